@@ -0,0 +1,252 @@
+//! Discovery across several AWS accounts (`--profiles`, `--profile-group`).
+
+use crate::cache::TopologyCache;
+use crate::config::Config;
+use crate::failure::Kind;
+use crate::picker::Picker;
+use crate::{
+    build_client, cached_clusters, find_service, list_services, matching_clusters, Args,
+    ClusterInfo, Origin,
+};
+use anyhow::{anyhow, Result};
+use console::style;
+use std::path::PathBuf;
+use tokio::task::JoinSet;
+
+// A cluster (and service) found under one of the profiles
+struct Candidate {
+    profile: String,
+    // The profile's label from the config file, if it has one
+    label: Option<String>,
+    region: Option<String>,
+    cluster: ClusterInfo,
+    service: Option<String>,
+}
+
+impl Candidate {
+    fn label(&self) -> String {
+        let mut label = format!(
+            "{} {}",
+            style(format!(
+                "[{}]",
+                self.label.as_ref().unwrap_or(&self.profile)
+            ))
+            .cyan(),
+            self.cluster.cluster_name
+        );
+        if let Some(service) = &self.service {
+            label.push('/');
+            label.push_str(service);
+        }
+        if let Some(stats) = self.cluster.stats() {
+            label.push_str(&format!("  ({})", stats));
+        }
+        label.push_str(&crate::arn::shown(&self.cluster.arn));
+        label
+    }
+}
+
+// The profiles of the group `name`, failing on any the AWS config files
+// don't define.
+pub fn expand_group(config: &Config, name: &str) -> Result<Vec<String>> {
+    let profiles = config.profile_group(name)?;
+    let missing = unknown_profiles(&profiles);
+    if !missing.is_empty() {
+        return Err(Kind::NotFound.error(format!(
+            "Profile group '{}' names profiles the AWS config doesn't define: {}",
+            name,
+            missing.join(", ")
+        )));
+    }
+    Ok(profiles)
+}
+
+// The ones of `profiles` that neither the AWS config nor the credentials
+// file defines. When neither file can be read nothing is known, and
+// nothing is reported, since the profiles may come from elsewhere.
+pub fn unknown_profiles(profiles: &[String]) -> Vec<String> {
+    let Some(known) = known_profiles() else {
+        return Vec::new();
+    };
+    profiles
+        .iter()
+        .filter(|profile| !known.contains(profile))
+        .cloned()
+        .collect()
+}
+
+// The profile names of the AWS config (`[profile NAME]`, `[default]`) and
+// credentials (`[NAME]`) files
+fn known_profiles() -> Option<Vec<String>> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    let file = |var: &str, name: &str| {
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(home.clone()?).join(".aws").join(name)))
+            .and_then(|path| std::fs::read_to_string(path).ok())
+    };
+    let config = file("AWS_CONFIG_FILE", "config");
+    let credentials = file("AWS_SHARED_CREDENTIALS_FILE", "credentials");
+    if config.is_none() && credentials.is_none() {
+        return None;
+    }
+    let headers = |contents: &str| -> Vec<String> {
+        contents
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix('[')?.strip_suffix(']'))
+            .map(|header| header.trim().to_string())
+            .collect()
+    };
+    let mut known: Vec<String> = config
+        .as_deref()
+        .map(headers)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|header| match header.strip_prefix("profile ") {
+            Some(name) => Some(name.trim().to_string()),
+            None => (header == "default").then_some(header),
+        })
+        .collect();
+    known.extend(credentials.as_deref().map(headers).unwrap_or_default());
+    Some(known)
+}
+
+// Pick a cluster across `args.profiles` and return the arguments with that
+// profile and cluster filled in.
+pub async fn resolve(mut args: Args, config: &Config) -> Result<Args> {
+    let shared = std::sync::Arc::new(args.clone());
+    let mut lookups = JoinSet::new();
+    for profile in &args.profiles {
+        let args = shared.clone();
+        let profile = profile.clone();
+        let label = config.profile_label(&profile);
+        let region = match &args.region {
+            Some(_) => None,
+            None => config.profile_region(&profile),
+        };
+        lookups.spawn(async move {
+            let found = candidates(&args, &profile, label, region).await;
+            (profile, found)
+        });
+    }
+
+    let mut candidates = Vec::new();
+    let mut failed = Vec::new();
+    while let Some(joined) = lookups.join_next().await {
+        match joined? {
+            (_, Ok(found)) => candidates.extend(found),
+            (profile, Err(err)) => {
+                eprintln!("Warning: skipping profile '{}': {:#}", profile, err);
+                failed.push(profile);
+            }
+        }
+    }
+    // Keep the order stable whatever finished first
+    failed.sort_by_key(|profile| args.profiles.iter().position(|p| p == profile));
+    candidates.sort_by(|a, b| {
+        let rank = |c: &Candidate| args.profiles.iter().position(|p| *p == c.profile);
+        rank(a)
+            .cmp(&rank(b))
+            .then_with(|| crate::natural::cmp(&a.cluster.cluster_name, &b.cluster.cluster_name))
+    });
+
+    if candidates.is_empty() {
+        let what = match &args.service {
+            Some(service) => format!("No cluster with service '{}'", service),
+            None => "No clusters".to_string(),
+        };
+        return Err(anyhow!(
+            "{} found under profiles {}{}",
+            what,
+            args.profiles.join(", "),
+            if failed.is_empty() {
+                String::new()
+            } else {
+                format!(" ({} failed)", failed.join(", "))
+            }
+        ));
+    }
+    let labels: Vec<String> = candidates.iter().map(Candidate::label).collect();
+    let chosen = match candidates.len() {
+        1 => 0,
+        _ => tokio::task::block_in_place(|| {
+            Picker::new("Select Cluster", &labels)
+                .highlight(args.cluster.as_deref())
+                .interact()
+        })?,
+    };
+    let chosen = candidates.swap_remove(chosen);
+
+    if let Some(region) = chosen.region {
+        args.origins.region = Origin::ProfileConfig(chosen.profile.clone());
+        args.region = Some(region);
+    }
+    args.profile = chosen.profile;
+    args.origins.profile = Origin::Flag;
+    args.cluster = Some(chosen.cluster.arn);
+    args.cluster_regex = None;
+    if chosen.service.is_some() {
+        args.service = chosen.service;
+        args.service_regex = None;
+    }
+    Ok(args)
+}
+
+// The clusters of one profile matching the flags, expanded to the wanted
+// service when --service is given
+async fn candidates(
+    args: &Args,
+    profile: &str,
+    label: Option<String>,
+    region: Option<String>,
+) -> Result<Vec<Candidate>> {
+    let client = build_client(profile, region.as_deref().or(args.region.as_deref())).await;
+    let region = client.config().region().map(|r| r.to_string());
+    let cache = TopologyCache::new(profile, region.as_deref(), args.cache_ttl);
+    let (clusters, _) = cached_clusters(&client, &cache, args, args.refresh).await?;
+
+    let clusters: Vec<ClusterInfo> = clusters
+        .into_iter()
+        .filter(|c| {
+            args.cluster_regex
+                .as_ref()
+                .is_none_or(|r| r.is_match(&c.cluster_name))
+        })
+        .collect();
+    // An exact name wins over the clusters it is only part of the name of
+    let clusters: Vec<ClusterInfo> = match args.cluster.as_deref() {
+        Some(name) => matching_clusters(&clusters, name)
+            .into_iter()
+            .cloned()
+            .collect(),
+        None => clusters,
+    };
+
+    let Some(service) = &args.service else {
+        return Ok(clusters
+            .into_iter()
+            .map(|cluster| Candidate {
+                profile: profile.to_string(),
+                label: label.clone(),
+                region: region.clone(),
+                cluster,
+                service: None,
+            })
+            .collect());
+    };
+    let mut found = Vec::new();
+    for cluster in clusters {
+        let services =
+            list_services(&client, &cluster.arn, Some(service), args.max_results).await?;
+        if let Some(service) = find_service(&services, service) {
+            found.push(Candidate {
+                profile: profile.to_string(),
+                label: label.clone(),
+                region: region.clone(),
+                cluster,
+                service: Some(service.service_name),
+            });
+        }
+    }
+    Ok(found)
+}
@@ -0,0 +1,361 @@
+//! Taking ECS ARNs apart, in every partition and both task ARN formats.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHOWN: AtomicBool = AtomicBool::new(false);
+
+// The fields of an ARN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arn<'a> {
+    pub partition: &'a str,
+    pub service: &'a str,
+    pub region: &'a str,
+    pub account: &'a str,
+    pub resource: &'a str,
+}
+
+impl<'a> Arn<'a> {
+    pub fn parse(arn: &'a str) -> Option<Arn<'a>> {
+        let fields: Vec<&str> = arn.splitn(6, ':').collect();
+        let ["arn", partition, service, region, account, resource] = fields[..] else {
+            return None;
+        };
+        if partition.is_empty() || service.is_empty() || resource.is_empty() {
+            return None;
+        }
+        Some(Arn {
+            partition,
+            service,
+            region,
+            account,
+            resource,
+        })
+    }
+
+    // The path after `<kind>/`, `["uat", "3f9c…"]` for `task/uat/3f9c…`,
+    // if the resource is of that kind and has between 1 and `max` segments.
+    pub fn path(&self, kind: &str, max: usize) -> Option<Vec<&'a str>> {
+        let path: Vec<&str> = self
+            .resource
+            .strip_prefix(kind)?
+            .strip_prefix('/')?
+            .split('/')
+            .collect();
+        if path.len() > max || path.iter().any(|segment| segment.is_empty()) {
+            return None;
+        }
+        Some(path)
+    }
+}
+
+// An ECS task ARN in either format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskArn<'a> {
+    pub region: &'a str,
+    // Only in new-format ARNs
+    pub cluster: Option<&'a str>,
+    pub task_id: &'a str,
+}
+
+pub fn task(arn: &str) -> Option<TaskArn<'_>> {
+    let parsed = Arn::parse(arn).filter(|arn| arn.service == "ecs" && !arn.region.is_empty())?;
+    match parsed.path("task", 2)?[..] {
+        [task_id] => Some(TaskArn {
+            region: parsed.region,
+            cluster: None,
+            task_id,
+        }),
+        [cluster, task_id] => Some(TaskArn {
+            region: parsed.region,
+            cluster: Some(cluster),
+            task_id,
+        }),
+        _ => None,
+    }
+}
+
+// `uat` for `arn:aws:ecs:eu-west-1:123456789012:cluster/uat`.
+pub fn cluster_name(value: &str) -> &str {
+    last_segment(value, "cluster", 1)
+}
+
+// The service name, with or without the cluster in the ARN.
+pub fn service_name(value: &str) -> &str {
+    last_segment(value, "service", 2)
+}
+
+// The task ID, with or without the cluster in the ARN.
+pub fn task_id(value: &str) -> &str {
+    last_segment(value, "task", 2)
+}
+
+// The container instance ID, with or without the cluster in the ARN.
+pub fn container_instance_id(value: &str) -> &str {
+    last_segment(value, "container-instance", 2)
+}
+
+// `web:42` for `arn:aws:ecs:eu-west-1:123456789012:task-definition/web:42`.
+pub fn task_definition(value: &str) -> &str {
+    last_segment(value, "task-definition", 1)
+}
+
+// The family of a task definition, `web` for `…:task-definition/web:42`.
+pub fn task_family(value: &str) -> &str {
+    let definition = task_definition(value);
+    definition
+        .split_once(':')
+        .map_or(definition, |(family, _)| family)
+}
+
+// The revision of a task definition, `42` for `…:task-definition/web:42`.
+pub fn task_revision(value: &str) -> Option<i32> {
+    task_definition(value).split_once(':')?.1.parse().ok()
+}
+
+// The region an ARN is in, when it names one.
+pub fn region(value: &str) -> Option<&str> {
+    Arn::parse(value)
+        .map(|arn| arn.region)
+        .filter(|region| !region.is_empty())
+}
+
+// Show the ARNs of picker entries (`--show-arns`).
+pub fn set_shown(shown: bool) {
+    SHOWN.store(shown, Ordering::Relaxed);
+}
+
+// What `--show-arns` appends to a picker entry, `  [task/uat/3f9c…,
+// 123456789012, eu-west-1]`, or nothing without the flag. Entries from
+// several accounts or regions can then be told apart.
+pub fn shown(value: &str) -> String {
+    if !SHOWN.load(Ordering::Relaxed) {
+        return String::new();
+    }
+    match Arn::parse(value) {
+        Some(arn) => console::style(format!(
+            "  [{}, {}, {}]",
+            arn.resource, arn.account, arn.region
+        ))
+        .dim()
+        .to_string(),
+        None => String::new(),
+    }
+}
+
+// The name or ID at the end of a `kind` ARN's path; anything else is taken
+// to be the name already
+fn last_segment<'a>(value: &'a str, kind: &str, max: usize) -> &'a str {
+    Arn::parse(value)
+        .and_then(|arn| arn.path(kind, max))
+        .and_then(|path| path.last().copied())
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (partition, region) of each partition ECS runs in
+    const PARTITIONS: [(&str, &str); 3] = [
+        ("aws", "eu-west-1"),
+        ("aws-cn", "cn-north-1"),
+        ("aws-us-gov", "us-gov-west-1"),
+    ];
+
+    // `arn:<partition>:ecs:<region>:123456789012:<resource>` in every partition
+    fn arns(resource: &str) -> Vec<(String, &'static str)> {
+        PARTITIONS
+            .iter()
+            .map(|(partition, region)| {
+                (
+                    format!("arn:{}:ecs:{}:123456789012:{}", partition, region, resource),
+                    *region,
+                )
+            })
+            .collect()
+    }
+
+    const MALFORMED: [&str; 8] = [
+        "",
+        "uat",
+        "arn:aws:ecs",
+        "arn::ecs:eu-west-1:123456789012:task/uat/abc",
+        "arn:aws::eu-west-1:123456789012:task/uat/abc",
+        "arn:aws:ecs:eu-west-1:123456789012:",
+        "arm:aws:ecs:eu-west-1:123456789012:task/uat/abc",
+        "task/uat/abc",
+    ];
+
+    #[test]
+    fn parse_splits_the_fields() {
+        for (arn, region) in arns("task/uat/abc") {
+            let parsed = Arn::parse(&arn).unwrap();
+            assert_eq!(
+                (
+                    parsed.service,
+                    parsed.region,
+                    parsed.account,
+                    parsed.resource
+                ),
+                ("ecs", region, "123456789012", "task/uat/abc"),
+                "{}",
+                arn
+            );
+            assert!(parsed.partition.starts_with("aws"));
+        }
+        // The resource keeps any further colons
+        let parsed = Arn::parse("arn:aws:ecs:eu-west-1:1:task-definition/web:42").unwrap();
+        assert_eq!(parsed.resource, "task-definition/web:42");
+        // Global services have no region
+        assert_eq!(Arn::parse("arn:aws:iam::1:role/x").unwrap().region, "");
+        for value in MALFORMED {
+            assert_eq!(Arn::parse(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn path_checks_kind_and_length() {
+        let cases: [(&str, &str, usize, Option<Vec<&str>>); 6] = [
+            ("task/uat/abc", "task", 2, Some(vec!["uat", "abc"])),
+            ("task/abc", "task", 2, Some(vec!["abc"])),
+            ("task/a/b/c", "task", 2, None),
+            ("task//abc", "task", 2, None),
+            ("task-definition/web:1", "task", 2, None),
+            ("service/uat/web", "cluster", 1, None),
+        ];
+        for (resource, kind, max, expected) in cases {
+            for (arn, _) in arns(resource) {
+                let parsed = Arn::parse(&arn).unwrap();
+                assert_eq!(parsed.path(kind, max), expected, "{}", arn);
+            }
+        }
+    }
+
+    #[test]
+    fn task_in_both_formats() {
+        let cases = [
+            ("task/uat/abc123", Some(Some("uat")), "abc123"),
+            ("task/abc123", Some(None), "abc123"),
+            ("task/a/b/c", None, ""),
+            ("task/", None, ""),
+            ("service/uat/web", None, ""),
+        ];
+        for (resource, cluster, task_id) in cases {
+            for (arn, region) in arns(resource) {
+                let expected = cluster.map(|cluster| TaskArn {
+                    region,
+                    cluster,
+                    task_id,
+                });
+                assert_eq!(task(&arn), expected, "{}", arn);
+            }
+        }
+        assert_eq!(task("arn:aws:ecs::123456789012:task/uat/abc123"), None);
+        assert_eq!(
+            task("arn:aws:ec2:eu-west-1:123456789012:task/uat/abc"),
+            None
+        );
+        for value in MALFORMED {
+            assert_eq!(task(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn cluster_name_of_arns_and_names() {
+        let cases = [
+            ("cluster/uat", "uat"),
+            ("cluster/app-staging", "app-staging"),
+        ];
+        for (resource, name) in cases {
+            for (arn, _) in arns(resource) {
+                assert_eq!(cluster_name(&arn), name, "{}", arn);
+            }
+        }
+        for value in ["uat", "cluster/uat"].iter().chain(&MALFORMED) {
+            assert_eq!(cluster_name(value), *value);
+        }
+        // Not a cluster ARN, so taken as a name
+        let (service, _) = &arns("service/uat/web")[0];
+        assert_eq!(cluster_name(service), service);
+    }
+
+    #[test]
+    fn service_name_with_and_without_cluster() {
+        let cases = [("service/uat/web", "web"), ("service/web", "web")];
+        for (resource, name) in cases {
+            for (arn, _) in arns(resource) {
+                assert_eq!(service_name(&arn), name, "{}", arn);
+            }
+        }
+        let (too_long, _) = &arns("service/a/b/c")[0];
+        assert_eq!(service_name(too_long), too_long);
+        for value in ["web"].iter().chain(&MALFORMED) {
+            assert_eq!(service_name(value), *value);
+        }
+    }
+
+    #[test]
+    fn task_id_in_both_formats() {
+        let cases = [("task/uat/abc123", "abc123"), ("task/abc123", "abc123")];
+        for (resource, id) in cases {
+            for (arn, _) in arns(resource) {
+                assert_eq!(task_id(&arn), id, "{}", arn);
+            }
+        }
+        for value in ["abc123"].iter().chain(&MALFORMED) {
+            assert_eq!(task_id(value), *value);
+        }
+    }
+
+    #[test]
+    fn container_instance_id_with_and_without_cluster() {
+        let cases = [
+            ("container-instance/uat/0a1b", "0a1b"),
+            ("container-instance/0a1b", "0a1b"),
+        ];
+        for (resource, id) in cases {
+            for (arn, _) in arns(resource) {
+                assert_eq!(container_instance_id(&arn), id, "{}", arn);
+            }
+        }
+        for value in ["0a1b"].iter().chain(&MALFORMED) {
+            assert_eq!(container_instance_id(value), *value);
+        }
+    }
+
+    #[test]
+    fn task_definition_family_and_revision() {
+        let cases = [
+            ("task-definition/web:42", "web:42", "web", Some(42)),
+            ("task-definition/web", "web", "web", None),
+            ("task-definition/web:latest", "web:latest", "web", None),
+        ];
+        for (resource, definition, family, revision) in cases {
+            for (arn, _) in arns(resource) {
+                assert_eq!(task_definition(&arn), definition, "{}", arn);
+                assert_eq!(task_family(&arn), family, "{}", arn);
+                assert_eq!(task_revision(&arn), revision, "{}", arn);
+            }
+        }
+        // Bare `family:revision` values
+        assert_eq!(task_family("web:7"), "web");
+        assert_eq!(task_revision("web:7"), Some(7));
+        for value in MALFORMED {
+            assert_eq!(task_definition(value), value);
+            assert_eq!(task_revision(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn region_of_each_partition() {
+        for resource in ["task/uat/abc", "service/web", "cluster/uat"] {
+            for (arn, region) in arns(resource) {
+                assert_eq!(super::region(&arn), Some(region), "{}", arn);
+            }
+        }
+        assert_eq!(super::region("arn:aws:iam::123456789012:role/x"), None);
+        for value in MALFORMED {
+            assert_eq!(super::region(value), None, "{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Calls to AWS services the SDK crates in this build don't cover, through
+//! the AWS CLI, and finding the CLI binary itself.
+
+use crate::credentials;
+use crate::json::{self, Value};
+use crate::proxy;
+use crate::tls;
+use anyhow::{anyhow, Result};
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+pub const ENV_VAR: &str = "AWS_CLI_PATH";
+
+const WINDOWS_BINARIES: [&str; 2] = ["aws.exe", "aws.cmd"];
+
+static BINARY: OnceLock<PathBuf> = OnceLock::new();
+static SESSION_ARGS: OnceLock<Vec<String>> = OnceLock::new();
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static FIPS: AtomicBool = AtomicBool::new(false);
+static DUALSTACK: AtomicBool = AtomicBool::new(false);
+
+// A command running the AWS CLI: `AWS_CLI_PATH` when it is set, else `aws`
+// from `PATH`.
+pub fn command() -> Command {
+    let mut cli = Command::new(BINARY.get_or_init(binary));
+    if use_fips() {
+        cli.env("AWS_USE_FIPS_ENDPOINT", "true");
+    }
+    if use_dualstack() {
+        cli.env("AWS_USE_DUALSTACK_ENDPOINT", "true");
+    }
+    cli
+}
+
+// Use FIPS endpoints: SDK configs ask for them and every CLI process gets
+// `AWS_USE_FIPS_ENDPOINT`.
+pub fn set_fips(fips: bool) {
+    FIPS.store(fips, Ordering::Relaxed);
+}
+
+pub fn use_fips() -> bool {
+    FIPS.load(Ordering::Relaxed)
+}
+
+// Use dual-stack (IPv6) endpoints, the same way as `set_fips`.
+pub fn set_dualstack(dualstack: bool) {
+    DUALSTACK.store(dualstack, Ordering::Relaxed);
+}
+
+pub fn use_dualstack() -> bool {
+    DUALSTACK.load(Ordering::Relaxed)
+}
+
+// Extra arguments (`--aws-args`) for `aws ecs execute-command`.
+pub fn set_session_args(args: Vec<String>) {
+    let _ = SESSION_ARGS.set(args);
+}
+
+// Append the extra arguments to an execute-command call. They come last,
+// so where they repeat one of our flags theirs wins.
+pub fn apply_session_args(cli: &mut Command) {
+    if let Some(args) = SESSION_ARGS.get() {
+        cli.args(args);
+    }
+}
+
+// Print every CLI command line to stderr before it runs.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+// With `--verbose`, show the command about to run, quoted for a shell.
+pub fn trace(cli: &Command) {
+    if !is_verbose() {
+        return;
+    }
+    let words: Vec<String> = std::iter::once(cli.get_program())
+        .chain(cli.get_args())
+        .map(|word| word.to_string_lossy().into_owned())
+        .collect();
+    eprintln!("+ {}", shell_words::join(words));
+}
+
+fn binary() -> PathBuf {
+    find_binary(
+        std::env::var_os(ENV_VAR),
+        std::env::var_os("PATH"),
+        cfg!(windows),
+    )
+}
+
+// The CLI given `AWS_CLI_PATH` and `PATH`. Windows installs are `aws.exe`,
+// or `aws.cmd` from pip, which `Command` won't find by the bare name.
+fn find_binary(cli_path: Option<OsString>, path: Option<OsString>, windows: bool) -> PathBuf {
+    if let Some(cli_path) = cli_path.filter(|cli_path| !cli_path.is_empty()) {
+        return PathBuf::from(cli_path);
+    }
+    if windows {
+        let found = path.and_then(|path| {
+            std::env::split_paths(&path)
+                .flat_map(|dir| WINDOWS_BINARIES.map(|name| dir.join(name)))
+                .find(|binary| binary.is_file())
+        });
+        if let Some(binary) = found {
+            return binary;
+        }
+    }
+    PathBuf::from("aws")
+}
+
+pub struct AwsCli<'a> {
+    profile: &'a str,
+    region: Option<&'a str>,
+}
+
+// A CLI invocation that exited unsuccessfully.
+#[derive(Debug)]
+pub struct CliError {
+    pub operation: String,
+    pub stderr: String,
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "aws {} failed: {}", self.operation, self.stderr.trim())
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl CliError {
+    pub fn is_access_denied(&self) -> bool {
+        [
+            "AccessDenied",
+            "UnauthorizedOperation",
+            "not authorized to perform",
+        ]
+        .iter()
+        .any(|needle| self.stderr.contains(needle))
+    }
+}
+
+// Whether `err` is a `--dry-run` call that would have succeeded.
+pub fn is_dry_run_success(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<CliError>()
+        .is_some_and(|err| err.stderr.contains("DryRunOperation"))
+}
+
+// Whether `err` is a CLI call rejected for missing permissions.
+pub fn is_access_denied(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<CliError>()
+        .is_some_and(CliError::is_access_denied)
+}
+
+impl<'a> AwsCli<'a> {
+    pub fn new(profile: &'a str, region: Option<&'a str>) -> Self {
+        AwsCli { profile, region }
+    }
+
+    // Run `aws <service> <operation> <args>` and parse its JSON output,
+    // without holding up a runtime worker while the CLI runs.
+    pub async fn call(&self, service: &str, operation: &str, args: &[String]) -> Result<Value> {
+        let mut cli = command();
+        cli.args([service, operation])
+            .args(args)
+            .args(["--output", "json"]);
+        credentials::apply(&mut cli, self.profile);
+        proxy::apply(&mut cli);
+        tls::apply(&mut cli);
+        if let Some(region) = self.region {
+            cli.args(["--region", region]);
+        }
+        trace(&cli);
+        let output = tokio::process::Command::from(cli)
+            .output()
+            .await
+            .map_err(|err| anyhow!("Cannot run the AWS CLI: {}", err))?;
+        if !output.status.success() {
+            return Err(CliError {
+                operation: format!("{} {}", service, operation),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        json::parse(&stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ecs_remote-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn binary_lookup_order() {
+        let empty = temp_dir("path-empty");
+        let dir = temp_dir("path-aws");
+        let path = Some(std::env::join_paths([&empty, &dir]).unwrap());
+        std::fs::write(dir.join("aws.cmd"), "@echo off\r\n").unwrap();
+        assert_eq!(find_binary(None, path.clone(), true), dir.join("aws.cmd"));
+
+        std::fs::write(dir.join("aws.exe"), "").unwrap();
+        assert_eq!(find_binary(None, path.clone(), true), dir.join("aws.exe"));
+
+        let pinned = Some(OsString::from("/opt/aws/bin/aws"));
+        assert_eq!(
+            find_binary(pinned, path.clone(), true),
+            PathBuf::from("/opt/aws/bin/aws")
+        );
+        // An empty AWS_CLI_PATH counts as unset
+        assert_eq!(
+            find_binary(Some(OsString::new()), path.clone(), true),
+            dir.join("aws.exe")
+        );
+
+        // Elsewhere `Command` searches PATH for `aws` itself
+        assert_eq!(find_binary(None, path, false), PathBuf::from("aws"));
+        assert_eq!(
+            find_binary(None, Some(empty.clone().into_os_string()), true),
+            PathBuf::from("aws")
+        );
+        let _ = std::fs::remove_dir_all(empty);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn command_asks_for_fips_and_dualstack_endpoints() {
+        set_fips(true);
+        set_dualstack(true);
+        let cli = command();
+        let envs: Vec<_> = cli.get_envs().collect();
+        for name in ["AWS_USE_FIPS_ENDPOINT", "AWS_USE_DUALSTACK_ENDPOINT"] {
+            assert!(
+                envs.contains(&(name.as_ref(), Some("true".as_ref()))),
+                "{} missing from {:?}",
+                name,
+                envs
+            );
+        }
+    }
+}
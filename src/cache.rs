@@ -0,0 +1,189 @@
+//! On-disk cache of the cluster and service lists (`topology.json`).
+
+use crate::json::Value;
+use crate::state;
+use crate::{ClusterInfo, ServiceInfo};
+use aws_sdk_ecs::types::Cluster;
+
+const TOPOLOGY_FILE: &str = "topology.json";
+
+#[derive(Debug, Clone)]
+pub struct TopologyCache {
+    key: String,
+    ttl: i64,
+}
+
+impl TopologyCache {
+    // A `ttl` of zero disables the cache.
+    pub fn new(profile: &str, region: Option<&str>, ttl: u64) -> Self {
+        TopologyCache {
+            key: format!("{}|{}", profile, region.unwrap_or("-")),
+            ttl: ttl as i64,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.ttl > 0
+    }
+
+    // Cached clusters, if the entry is still fresh.
+    pub fn clusters(&self) -> Option<Vec<ClusterInfo>> {
+        let items = self.fresh(&["clusters"])?;
+        Some(items.iter().filter_map(cluster_from_json).collect())
+    }
+
+    pub fn store_clusters(&self, clusters: &[ClusterInfo]) {
+        let items = clusters.iter().map(cluster_to_json).collect();
+        self.store(&["clusters"], items);
+    }
+
+    // Cached services of a cluster, if the entry is still fresh.
+    pub fn services(&self, cluster_arn: &str) -> Option<Vec<ServiceInfo>> {
+        let items = self.fresh(&["services", cluster_arn])?;
+        Some(
+            items
+                .iter()
+                .filter_map(|item| {
+                    Some(ServiceInfo {
+                        arn: item.str_field("arn")?,
+                        service_name: item.str_field("service_name")?,
+                        details: None,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    pub fn store_services(&self, cluster_arn: &str, services: &[ServiceInfo]) {
+        let items = services
+            .iter()
+            .map(|service| {
+                let mut item = Value::object();
+                item.set("arn", &service.arn);
+                item.set("service_name", &service.service_name);
+                item
+            })
+            .collect();
+        self.store(&["services", cluster_arn], items);
+    }
+
+    // Cached task definition families of a cluster's services, as
+    // `(service name, family)` pairs, if the entry is still fresh.
+    pub fn families(&self, cluster_arn: &str) -> Option<Vec<(String, String)>> {
+        let items = self.fresh(&["families", cluster_arn])?;
+        Some(
+            items
+                .iter()
+                .filter_map(|item| {
+                    Some((item.str_field("service_name")?, item.str_field("family")?))
+                })
+                .collect(),
+        )
+    }
+
+    pub fn store_families(&self, cluster_arn: &str, families: &[(String, String)]) {
+        let items = families
+            .iter()
+            .map(|(service_name, family)| {
+                let mut item = Value::object();
+                item.set("service_name", service_name);
+                item.set("family", family);
+                item
+            })
+            .collect();
+        self.store(&["families", cluster_arn], items);
+    }
+
+    fn fresh(&self, path: &[&str]) -> Option<Vec<Value>> {
+        if !self.enabled() {
+            return None;
+        }
+        let root = state::read(TOPOLOGY_FILE).ok()?;
+        let mut entry = root.get(&self.key)?;
+        for key in path {
+            entry = entry.get(key)?;
+        }
+        let fetched_at = entry.get("fetched_at")?.as_i64()?;
+        if state::now_secs() - fetched_at > self.ttl {
+            return None;
+        }
+        entry.get("items")?.as_array().cloned()
+    }
+
+    // Caching is best-effort; a failed write just means a slower next run
+    fn store(&self, path: &[&str], items: Vec<Value>) {
+        if !self.enabled() {
+            return;
+        }
+        let mut entry = Value::object();
+        entry.set("fetched_at", state::now_secs());
+        entry.set("items", items);
+
+        let _ = state::update(TOPOLOGY_FILE, |root| {
+            if !matches!(root, Value::Object(_)) {
+                *root = Value::object();
+            }
+            set_path(root, &self.key, path, entry);
+        });
+    }
+}
+
+fn set_path(value: &mut Value, key: &str, path: &[&str], entry: Value) {
+    if value
+        .get(key)
+        .is_none_or(|v| !matches!(v, Value::Object(_)))
+    {
+        value.set(key, Value::object());
+    }
+    let Value::Object(entries) = value else {
+        return;
+    };
+    let Some((_, child)) = entries.iter_mut().find(|(k, _)| k == key) else {
+        return;
+    };
+    match path.split_first() {
+        Some((next, rest)) => set_path(child, next, rest, entry),
+        None => *child = entry,
+    }
+}
+
+fn cluster_to_json(cluster: &ClusterInfo) -> Value {
+    let mut item = Value::object();
+    item.set("arn", &cluster.arn);
+    item.set("cluster_name", &cluster.cluster_name);
+    if let Some(details) = &cluster.details {
+        let mut counts = Value::object();
+        if let Some(status) = &details.status {
+            counts.set("status", status);
+        }
+        counts.set("active_services_count", details.active_services_count);
+        counts.set("running_tasks_count", details.running_tasks_count);
+        counts.set("pending_tasks_count", details.pending_tasks_count);
+        counts.set(
+            "registered_container_instances_count",
+            details.registered_container_instances_count,
+        );
+        item.set("details", counts);
+    }
+    item
+}
+
+fn cluster_from_json(item: &Value) -> Option<ClusterInfo> {
+    let arn = item.str_field("arn")?;
+    let details = item.get("details").map(|counts| {
+        let count = |key: &str| counts.get(key).and_then(Value::as_i64).unwrap_or(0) as i32;
+        Cluster::builder()
+            .cluster_arn(&arn)
+            .set_status(counts.str_field("status"))
+            .active_services_count(count("active_services_count"))
+            .running_tasks_count(count("running_tasks_count"))
+            .pending_tasks_count(count("pending_tasks_count"))
+            .registered_container_instances_count(count("registered_container_instances_count"))
+            .build()
+    });
+    Some(ClusterInfo {
+        cluster_name: item.str_field("cluster_name")?,
+        arn,
+        details,
+    })
+}
@@ -0,0 +1,1223 @@
+//! Diagnostics for the things that make ECS Exec fail.
+
+use crate::awscli::{self, AwsCli};
+use crate::json::Value;
+use crate::plugin;
+use crate::ratelimit;
+use anyhow::Result;
+use aws_sdk_ecs::types::{
+    ClusterField, ExecuteCommandConfiguration, ExecuteCommandLogging, LaunchType, Task,
+};
+use aws_sdk_ecs::Client;
+use console::style;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Info,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub level: Level,
+    pub message: String,
+    // Command or console link that fixes the problem.
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    pub fn new(level: Level, message: impl Into<String>) -> Self {
+        Finding {
+            level,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    pub fn with_fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+
+    // The finding as report lines, for stdout (`check`) or stderr
+    // (`--diagnose`, whose stdout may be the session's output).
+    pub fn lines(&self) -> Vec<String> {
+        let tag = match self.level {
+            Level::Ok => style(" ok ").green(),
+            Level::Info => style("info").cyan(),
+            Level::Warn => style("warn").yellow(),
+            Level::Fail => style("FAIL").red().bold(),
+        };
+        let mut lines = vec![format!("[{}] {}", tag, self.message)];
+        if let Some(fix) = &self.fix {
+            lines.push(format!("       {} {}", style("fix:").bold(), fix));
+        }
+        lines
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut value = Value::object();
+        let level = match self.level {
+            Level::Ok => "ok",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Fail => "fail",
+        };
+        value.set("level", level);
+        value.set("message", &self.message);
+        if let Some(fix) = &self.fix {
+            value.set("fix", fix);
+        }
+        value
+    }
+}
+
+// Where a task lives, for the commands and links that fix its problems.
+pub struct Scope<'a> {
+    pub profile: &'a str,
+    pub region: &'a str,
+    pub cluster: &'a str,
+    pub service: &'a str,
+}
+
+impl Scope<'_> {
+    fn aws(&self, command: &str) -> String {
+        format!(
+            "aws {} --profile {} --region {}",
+            command, self.profile, self.region
+        )
+    }
+
+    fn force_new_deployment(&self, extra: &str) -> String {
+        if self.service.is_empty() {
+            return "start a new task with --enable-execute-command".to_string();
+        }
+        self.aws(&format!(
+            "ecs update-service --cluster {} --service {}{} --force-new-deployment",
+            self.cluster, self.service, extra
+        ))
+    }
+}
+
+// The cluster's `executeCommandConfiguration`, if it has one.
+pub async fn exec_config(
+    client: &Client,
+    cluster_arn: &str,
+) -> Result<Option<ExecuteCommandConfiguration>> {
+    ratelimit::acquire().await;
+    let response = client
+        .describe_clusters()
+        .clusters(cluster_arn)
+        .include(ClusterField::Configurations)
+        .send()
+        .await?;
+    Ok(response
+        .clusters
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .and_then(|cluster| cluster.configuration)
+        .and_then(|configuration| configuration.execute_command_configuration))
+}
+
+// One line per setting of the exec configuration, for a quick look.
+pub fn describe_exec_config(config: Option<&ExecuteCommandConfiguration>) -> Vec<String> {
+    let Some(config) = config else {
+        return vec!["logging: DEFAULT (no exec configuration set)".to_string()];
+    };
+    let mut lines = vec![format!("logging: {}", logging_mode(config))];
+    if let Some(log) = &config.log_configuration {
+        if let Some(group) = &log.cloud_watch_log_group_name {
+            lines.push(format!(
+                "CloudWatch log group: {}{}",
+                group,
+                if log.cloud_watch_encryption_enabled {
+                    " (encrypted)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        if let Some(bucket) = &log.s3_bucket_name {
+            let prefix = log.s3_key_prefix.as_deref().unwrap_or_default();
+            lines.push(format!(
+                "S3 bucket: s3://{}/{}{}",
+                bucket,
+                prefix,
+                if log.s3_encryption_enabled {
+                    " (encrypted)"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+    lines.push(format!(
+        "KMS key: {}",
+        config.kms_key_id.as_deref().unwrap_or("none (AWS managed)")
+    ));
+    lines
+}
+
+fn logging_mode(config: &ExecuteCommandConfiguration) -> &str {
+    config
+        .logging
+        .as_ref()
+        .map(ExecuteCommandLogging::as_str)
+        .unwrap_or("DEFAULT")
+}
+
+// Check the exec configuration and that its log destinations exist.
+pub async fn exec_config_findings(
+    config: Option<&ExecuteCommandConfiguration>,
+    cli: &AwsCli<'_>,
+) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = describe_exec_config(config)
+        .into_iter()
+        .map(|line| Finding::new(Level::Info, line))
+        .collect();
+    let Some(config) = config else {
+        return findings;
+    };
+
+    let log = config.log_configuration.as_ref();
+    let group = log.and_then(|l| l.cloud_watch_log_group_name.as_deref());
+    let bucket = log.and_then(|l| l.s3_bucket_name.as_deref());
+    match config.logging {
+        Some(ExecuteCommandLogging::Override) if group.is_none() && bucket.is_none() => {
+            findings.push(
+                Finding::new(
+                    Level::Fail,
+                    "logging is OVERRIDE but neither a log group nor a bucket is configured",
+                )
+                .with_fix(
+                    "set logConfiguration in the cluster's executeCommandConfiguration, or logging DEFAULT",
+                ),
+            );
+        }
+        Some(ExecuteCommandLogging::None) | Some(ExecuteCommandLogging::Default) | None
+            if group.is_some() || bucket.is_some() =>
+        {
+            findings.push(Finding::new(
+                Level::Warn,
+                format!(
+                    "log destinations are configured but ignored while logging is {}",
+                    logging_mode(config)
+                ),
+            ));
+        }
+        _ => {}
+    }
+    if config.logging != Some(ExecuteCommandLogging::Override) {
+        return findings;
+    }
+
+    if let Some(group) = group {
+        findings.push(log_group_finding(cli, group).await);
+    }
+    if let Some(bucket) = bucket {
+        findings.push(bucket_finding(cli, bucket).await);
+    }
+    findings
+}
+
+// Retention periods CloudWatch Logs accepts, in days.
+pub const RETENTION_DAYS: [u32; 22] = [
+    1, 3, 5, 7, 14, 30, 60, 90, 120, 150, 180, 365, 400, 545, 731, 1096, 1827, 2192, 2557, 2922,
+    3288, 3653,
+];
+
+// The CloudWatch log group sessions are logged to, when logging is on and
+// goes there.
+pub fn log_group(config: Option<&ExecuteCommandConfiguration>) -> Option<&str> {
+    let config = config?;
+    if config.logging != Some(ExecuteCommandLogging::Override) {
+        return None;
+    }
+    config
+        .log_configuration
+        .as_ref()?
+        .cloud_watch_log_group_name
+        .as_deref()
+}
+
+pub async fn log_group_exists(cli: &AwsCli<'_>, group: &str) -> Result<bool> {
+    let response = cli
+        .call(
+            "logs",
+            "describe-log-groups",
+            &["--log-group-name-prefix".to_string(), group.to_string()],
+        )
+        .await?;
+    Ok(response
+        .get("logGroups")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .any(|g| g.get("logGroupName").and_then(Value::as_str) == Some(group)))
+}
+
+// Create the log group (and set its retention), naming the IAM action
+// that is missing when the profile may not.
+pub async fn create_log_group(cli: &AwsCli<'_>, group: &str, retention: Option<u32>) -> Finding {
+    let name = ["--log-group-name".to_string(), group.to_string()];
+    let denied = |level: Level, what: String, action: &str| {
+        Finding::new(level, format!("{} ({} denied)", what, action)).with_fix(format!(
+            "allow {} on arn:aws:logs:*:*:log-group:{}",
+            action, group
+        ))
+    };
+    match cli.call("logs", "create-log-group", &name).await {
+        Ok(_) => {}
+        Err(err) if awscli::is_access_denied(&err) => {
+            return denied(
+                Level::Fail,
+                format!("cannot create log group {}", group),
+                "logs:CreateLogGroup",
+            )
+        }
+        Err(err) => {
+            return Finding::new(
+                Level::Fail,
+                format!("cannot create log group {}: {}", group, err),
+            )
+        }
+    }
+    let Some(days) = retention else {
+        return Finding::new(Level::Ok, format!("created log group {}", group));
+    };
+    let mut args = name.to_vec();
+    args.extend(["--retention-in-days".to_string(), days.to_string()]);
+    match cli.call("logs", "put-retention-policy", &args).await {
+        Ok(_) => Finding::new(
+            Level::Ok,
+            format!("created log group {} with {}-day retention", group, days),
+        ),
+        Err(err) if awscli::is_access_denied(&err) => denied(
+            Level::Warn,
+            format!("created log group {} but cannot set its retention", group),
+            "logs:PutRetentionPolicy",
+        ),
+        Err(err) => Finding::new(
+            Level::Warn,
+            format!(
+                "created log group {} but could not set its retention: {}",
+                group, err
+            ),
+        ),
+    }
+}
+
+async fn log_group_finding(cli: &AwsCli<'_>, group: &str) -> Finding {
+    match log_group_exists(cli, group).await {
+        Ok(true) => Finding::new(Level::Ok, format!("log group {} exists", group)),
+        Ok(false) => Finding::new(
+            Level::Fail,
+            format!(
+                "log group {} does not exist; sessions will fail to start",
+                group
+            ),
+        )
+        .with_fix(format!(
+            "ecs_remote check --create-log-group, or aws logs create-log-group --log-group-name {}",
+            group
+        )),
+        Err(err) if awscli::is_access_denied(&err) => Finding::new(
+            Level::Warn,
+            format!(
+                "cannot check log group {} (logs:DescribeLogGroups denied)",
+                group
+            ),
+        ),
+        Err(err) => Finding::new(
+            Level::Warn,
+            format!("cannot check log group {}: {}", group, err),
+        ),
+    }
+}
+
+async fn bucket_finding(cli: &AwsCli<'_>, bucket: &str) -> Finding {
+    match cli
+        .call(
+            "s3api",
+            "head-bucket",
+            &["--bucket".to_string(), bucket.to_string()],
+        )
+        .await
+    {
+        Ok(_) => Finding::new(Level::Ok, format!("bucket {} exists", bucket)),
+        Err(err) => {
+            let text = err.to_string();
+            if text.contains("404") || text.contains("Not Found") {
+                Finding::new(
+                    Level::Fail,
+                    format!(
+                        "bucket {} does not exist; sessions will fail to start",
+                        bucket
+                    ),
+                )
+                .with_fix(format!("aws s3 mb s3://{}", bucket))
+            } else if text.contains("403") || awscli::is_access_denied(&err) {
+                Finding::new(
+                    Level::Warn,
+                    format!("bucket {} exists but this profile can't access it", bucket),
+                )
+            } else {
+                Finding::new(
+                    Level::Warn,
+                    format!("cannot check bucket {}: {}", bucket, err),
+                )
+            }
+        }
+    }
+}
+
+// Check that `principal` can use the session encryption key. DescribeKey
+// tells whether the key is usable at all; GenerateDataKey is what the
+// client needs to start an encrypted session and is tried as a dry run.
+pub async fn kms_findings(cli: &AwsCli<'_>, key_id: &str, principal: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let key_args = ["--key-id".to_string(), key_id.to_string()];
+    match cli.call("kms", "describe-key", &key_args).await {
+        Ok(response) => {
+            let metadata = response.get("KeyMetadata");
+            let state = metadata
+                .and_then(|m| m.str_field("KeyState"))
+                .unwrap_or_else(|| "Unknown".to_string());
+            if state == "Enabled" {
+                findings.push(Finding::new(
+                    Level::Ok,
+                    format!("KMS key {} is enabled", key_id),
+                ));
+            } else {
+                findings.push(Finding::new(
+                    Level::Fail,
+                    format!(
+                        "KMS key {} is {}; sessions can't be encrypted with it",
+                        key_id, state
+                    ),
+                ));
+            }
+        }
+        // Describing may be denied while using the key is allowed, so go on
+        Err(err) if awscli::is_access_denied(&err) => findings.push(Finding::new(
+            Level::Warn,
+            format!(
+                "{} is missing kms:DescribeKey on {}; can't tell whether the key is enabled",
+                principal, key_id
+            ),
+        )),
+        Err(err) => findings.push(Finding::new(
+            Level::Fail,
+            format!("cannot describe KMS key {}: {}", key_id, err),
+        )),
+    }
+
+    let mut generate_args = key_args.to_vec();
+    generate_args.extend([
+        "--key-spec".to_string(),
+        "AES_256".to_string(),
+        "--dry-run".to_string(),
+    ]);
+    match cli.call("kms", "generate-data-key", &generate_args).await {
+        Ok(_) => findings.push(Finding::new(
+            Level::Ok,
+            format!("{} can use KMS key {}", principal, key_id),
+        )),
+        Err(err) if awscli::is_dry_run_success(&err) => findings.push(Finding::new(
+            Level::Ok,
+            format!("{} can use KMS key {}", principal, key_id),
+        )),
+        Err(err) if awscli::is_access_denied(&err) => findings.push(
+            Finding::new(
+                Level::Fail,
+                format!(
+                    "{} is missing kms:GenerateDataKey on {}; sessions will fail to start",
+                    principal, key_id
+                ),
+            )
+            .with_fix(format!(
+                "allow kms:GenerateDataKey on {} in the key policy or the caller's IAM policy",
+                key_id
+            )),
+        ),
+        Err(err) => findings.push(Finding::new(
+            Level::Warn,
+            format!("cannot test kms:GenerateDataKey on {}: {}", key_id, err),
+        )),
+    }
+    findings.push(Finding::new(
+        Level::Info,
+        format!("the task role also needs kms:Decrypt on {}", key_id),
+    ));
+    findings
+}
+
+// Where session-manager-plugin is and which version it is.
+pub fn plugin_findings() -> Vec<Finding> {
+    let Some(binary) = plugin::resolve() else {
+        return vec![Finding::new(
+            Level::Fail,
+            "session-manager-plugin not found on PATH; install it or pass --plugin-path",
+        )
+        .with_fix("https://docs.aws.amazon.com/systems-manager/latest/userguide/session-manager-working-with-install-plugin.html")];
+    };
+    match plugin::version(&binary) {
+        Some(version) => vec![Finding::new(
+            Level::Ok,
+            format!("session-manager-plugin {} at {}", version, binary.display()),
+        )],
+        None => vec![Finding::new(
+            Level::Warn,
+            format!(
+                "session-manager-plugin at {} does not report a version",
+                binary.display()
+            ),
+        )],
+    }
+}
+
+// Whether the task is still running.
+pub fn task_running_findings(task: &Task, scope: &Scope) -> Vec<Finding> {
+    match task.last_status.as_deref() {
+        Some("RUNNING") => vec![Finding::new(Level::Ok, "task is RUNNING")],
+        status => vec![Finding::new(
+            Level::Fail,
+            format!(
+                "task is {}; it can't take sessions any more",
+                status.unwrap_or("gone")
+            ),
+        )
+        .with_fix(format!(
+            "pick another task: ecs_remote -l {} -s {}",
+            scope.cluster, scope.service
+        ))],
+    }
+}
+
+// Whether the task was started with execute command enabled.
+pub fn exec_flag_findings(task: &Task, scope: &Scope) -> Vec<Finding> {
+    if task.enable_execute_command {
+        return vec![Finding::new(Level::Ok, "execute command is enabled")];
+    }
+    vec![Finding::new(
+        Level::Fail,
+        "the task was started without execute command enabled",
+    )
+    .with_fix(scope.force_new_deployment(" --enable-execute-command"))]
+}
+
+// The status of the exec agent in `container`.
+pub fn agent_findings(task: &Task, container: &str, scope: &Scope) -> Vec<Finding> {
+    let status = crate::exec_agents(task)
+        .into_iter()
+        .find(|(name, _)| name == container)
+        .map(|(_, status)| status);
+    match status.as_deref() {
+        Some("RUNNING") => vec![Finding::new(
+            Level::Ok,
+            format!("exec agent in {} is RUNNING", container),
+        )],
+        Some("STOPPED") => vec![Finding::new(
+            Level::Fail,
+            format!("exec agent in {} has stopped", container),
+        )
+        .with_fix(scope.force_new_deployment(""))],
+        Some(status) => vec![Finding::new(
+            Level::Fail,
+            format!("exec agent in {} is {}, not RUNNING yet", container, status),
+        )
+        .with_fix("wait a minute for the agent to start, then connect again")],
+        None => vec![Finding::new(
+            Level::Fail,
+            format!("container {} reports no exec agent", container),
+        )
+        .with_fix(scope.force_new_deployment(" --enable-execute-command"))],
+    }
+}
+
+// Fargate needs platform 1.4.0 or later (1.0.0 on Windows) for ECS Exec.
+pub fn platform_findings(task: &Task, scope: &Scope) -> Vec<Finding> {
+    if task.launch_type != Some(LaunchType::Fargate) {
+        return vec![Finding::new(
+            Level::Ok,
+            "not a Fargate task; no platform version requirement",
+        )];
+    }
+    let Some(version) = task.platform_version.as_deref() else {
+        return vec![Finding::new(
+            Level::Warn,
+            "Fargate task without a platform version",
+        )];
+    };
+    let minimum = minimum_platform(task);
+    let Some(current) = parse_version(version) else {
+        return vec![Finding::new(
+            Level::Warn,
+            format!("unrecognized Fargate platform version {}", version),
+        )];
+    };
+    if current >= minimum {
+        return vec![Finding::new(
+            Level::Ok,
+            format!("Fargate platform version {}", version),
+        )];
+    }
+    vec![Finding::new(
+        Level::Fail,
+        format!(
+            "Fargate platform version {} is too old for ECS Exec (needs {}.{}.{})",
+            version, minimum.0, minimum.1, minimum.2
+        ),
+    )
+    .with_fix(scope.force_new_deployment(" --platform-version LATEST"))]
+}
+
+// Whether the task runs on a Fargate platform version ECS Exec can't reach.
+pub fn platform_too_old(task: &Task) -> bool {
+    task.launch_type == Some(LaunchType::Fargate)
+        && task
+            .platform_version
+            .as_deref()
+            .and_then(parse_version)
+            .is_some_and(|current| current < minimum_platform(task))
+}
+
+// Whether the task runs on an ECS Anywhere (EXTERNAL) host.
+pub fn is_external(task: &Task) -> bool {
+    task.launch_type == Some(LaunchType::External)
+}
+
+// An ECS Anywhere host as SSM knows it.
+#[derive(Debug, Clone)]
+pub struct ManagedInstance {
+    // Online, ConnectionLost or Inactive
+    pub ping_status: String,
+    // The name it was registered with, else its computer name
+    pub name: Option<String>,
+}
+
+// Look up managed instances (`mi-…`, the "EC2 instance" IDs of external
+// container instances) in SSM.
+pub async fn managed_instances(
+    cli: &AwsCli<'_>,
+    ids: &[String],
+) -> Result<std::collections::HashMap<String, ManagedInstance>> {
+    let response = cli
+        .call(
+            "ssm",
+            "describe-instance-information",
+            &[
+                "--filters".to_string(),
+                format!("Key=InstanceIds,Values={}", ids.join(",")),
+            ],
+        )
+        .await?;
+    Ok(response
+        .get("InstanceInformationList")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|info| {
+            let instance = ManagedInstance {
+                ping_status: info.str_field("PingStatus")?,
+                name: info
+                    .str_field("Name")
+                    .filter(|name| !name.is_empty())
+                    .or_else(|| info.str_field("ComputerName")),
+            };
+            Some((info.str_field("InstanceId")?, instance))
+        })
+        .collect())
+}
+
+// Whether the SSM agents on the ECS Anywhere hosts of `tasks` are online:
+// sessions into EXTERNAL tasks go through the host's agent, and one that
+// stopped pinging SSM is the usual reason they fail.
+pub async fn external_host_findings(
+    client: &Client,
+    cli: &AwsCli<'_>,
+    cluster_arn: &str,
+    tasks: &[Task],
+) -> Vec<Finding> {
+    let mut arns: Vec<String> = tasks
+        .iter()
+        .filter(|task| is_external(task))
+        .filter_map(|task| task.container_instance_arn.clone())
+        .collect();
+    arns.sort_unstable();
+    arns.dedup();
+    if arns.is_empty() {
+        return Vec::new();
+    }
+    ratelimit::acquire().await;
+    let described = match client
+        .describe_container_instances()
+        .cluster(cluster_arn)
+        .set_container_instances(Some(arns))
+        .send()
+        .await
+    {
+        Ok(described) => described,
+        Err(err) => {
+            return vec![Finding::new(
+                Level::Warn,
+                format!(
+                    "cannot describe the external container instances: {}",
+                    aws_sdk_ecs::error::DisplayErrorContext(&err)
+                ),
+            )]
+        }
+    };
+    let ids: Vec<String> = described
+        .container_instances
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|instance| instance.ec2_instance_id)
+        .collect();
+    if ids.is_empty() {
+        return Vec::new();
+    }
+    let instances = match managed_instances(cli, &ids).await {
+        Ok(instances) => instances,
+        Err(err) => {
+            let reason = if awscli::is_access_denied(&err) {
+                "ssm:DescribeInstanceInformation denied".to_string()
+            } else {
+                err.to_string()
+            };
+            return vec![Finding::new(
+                Level::Warn,
+                format!(
+                    "cannot check the SSM agents of the external instances ({})",
+                    reason
+                ),
+            )];
+        }
+    };
+    ids.iter()
+        .map(|id| {
+            let Some(instance) = instances.get(id) else {
+                return Finding::new(
+                    Level::Fail,
+                    format!("external instance {} is not registered with SSM", id),
+                )
+                .with_fix("register the host again with the ECS Anywhere install script and a new SSM activation");
+            };
+            let host = match &instance.name {
+                Some(name) => format!("{} ({})", id, name),
+                None => id.clone(),
+            };
+            if instance.ping_status == "Online" {
+                Finding::new(
+                    Level::Ok,
+                    format!("SSM agent on external instance {} is Online", host),
+                )
+            } else {
+                Finding::new(
+                    Level::Fail,
+                    format!(
+                        "SSM agent on external instance {} is {}; sessions can't reach its tasks",
+                        host, instance.ping_status
+                    ),
+                )
+                .with_fix("on the host: sudo systemctl restart amazon-ssm-agent, and check that it can reach ssm and ssmmessages")
+            }
+        })
+        .collect()
+}
+
+fn minimum_platform(task: &Task) -> (u32, u32, u32) {
+    let windows = task
+        .platform_family
+        .as_deref()
+        .is_some_and(|family| family.starts_with("WINDOWS"));
+    if windows {
+        (1, 0, 0)
+    } else {
+        (1, 4, 0)
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u32, u32, u32)> {
+    let parsed: Vec<u32> = version.split('.').filter_map(|n| n.parse().ok()).collect();
+    match parsed[..] {
+        [major, minor, patch] => Some((major, minor, patch)),
+        _ => None,
+    }
+}
+
+// Whether `principal` may call ecs:ExecuteCommand on the task, by asking
+// IAM to simulate its identity policies. SCPs and session policies aren't
+// part of the simulation.
+pub async fn execute_permission_findings(
+    cli: &AwsCli<'_>,
+    principal: &str,
+    task_arn: &str,
+) -> Vec<Finding> {
+    let Some(source) = policy_source(principal) else {
+        return vec![Finding::new(
+            Level::Warn,
+            format!("cannot simulate the policies of {}", principal),
+        )];
+    };
+    let decisions = match simulate(cli, &source, &["ecs:ExecuteCommand"], task_arn).await {
+        Ok(decisions) => decisions,
+        Err(err) if awscli::is_access_denied(&err) => {
+            return vec![Finding::new(
+                Level::Warn,
+                format!(
+                    "cannot check ecs:ExecuteCommand (iam:SimulatePrincipalPolicy denied for {})",
+                    principal
+                ),
+            )]
+        }
+        Err(err) => {
+            return vec![Finding::new(
+                Level::Warn,
+                format!("cannot check ecs:ExecuteCommand: {}", err),
+            )]
+        }
+    };
+    let decision = decisions.into_iter().next().map(|(_, decision)| decision);
+    match decision.as_deref() {
+        Some("allowed") => vec![Finding::new(
+            Level::Ok,
+            format!("{} may call ecs:ExecuteCommand on the task", principal),
+        )],
+        Some(decision) => vec![Finding::new(
+            Level::Fail,
+            format!(
+                "{} may not call ecs:ExecuteCommand on the task ({})",
+                principal, decision
+            ),
+        )
+        .with_fix(format!(
+            "allow ecs:ExecuteCommand on {} in the policies of {}: https://console.aws.amazon.com/iam/home#/roles",
+            task_arn, source
+        ))],
+        None => vec![Finding::new(
+            Level::Warn,
+            "IAM returned no decision for ecs:ExecuteCommand",
+        )],
+    }
+}
+
+// The task role's side of the SSM channel
+const SSM_ACTIONS: &[&str] = &[
+    "ssmmessages:CreateControlChannel",
+    "ssmmessages:CreateDataChannel",
+    "ssmmessages:OpenControlChannel",
+    "ssmmessages:OpenDataChannel",
+];
+
+// Whether the task role may open the SSM channels, simulated per action.
+// Without iam:SimulatePrincipalPolicy this can't be verified, so the policy
+// statement to add is printed either way.
+pub async fn ssm_permission_findings(cli: &AwsCli<'_>, role_arn: &str) -> Vec<Finding> {
+    let statement = format!(
+        r#"{{"Effect": "Allow", "Action": [{}], "Resource": "*"}}"#,
+        SSM_ACTIONS
+            .iter()
+            .map(|action| format!("\"{}\"", action))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let decisions = match simulate(cli, role_arn, SSM_ACTIONS, "*").await {
+        Ok(decisions) => decisions,
+        Err(err) => {
+            let reason = if awscli::is_access_denied(&err) {
+                "iam:SimulatePrincipalPolicy denied".to_string()
+            } else {
+                err.to_string()
+            };
+            return vec![Finding::new(
+                Level::Warn,
+                format!(
+                    "could not verify the ssmmessages permissions of {} ({})",
+                    role_arn, reason
+                ),
+            )
+            .with_fix(format!("make sure the task role has {}", statement))];
+        }
+    };
+    let mut findings: Vec<Finding> = decisions
+        .iter()
+        .map(|(action, decision)| {
+            if decision == "allowed" {
+                Finding::new(Level::Ok, format!("task role may call {}", action))
+            } else {
+                Finding::new(
+                    Level::Fail,
+                    format!("task role may not call {} ({})", action, decision),
+                )
+            }
+        })
+        .collect();
+    if let Some(last) = findings.iter_mut().rev().find(|f| f.level == Level::Fail) {
+        last.fix = Some(format!(
+            "add to the policies of {}: {}",
+            role_arn, statement
+        ));
+    }
+    findings
+}
+
+// Ask IAM how `source`'s identity policies decide each action on
+// `resource`, as (action, decision) pairs
+async fn simulate(
+    cli: &AwsCli<'_>,
+    source: &str,
+    actions: &[&str],
+    resource: &str,
+) -> Result<Vec<(String, String)>> {
+    let mut call_args = vec![
+        "--policy-source-arn".to_string(),
+        source.to_string(),
+        "--action-names".to_string(),
+    ];
+    call_args.extend(actions.iter().map(|action| action.to_string()));
+    call_args.extend(["--resource-arns".to_string(), resource.to_string()]);
+    let response = cli
+        .call("iam", "simulate-principal-policy", &call_args)
+        .await?;
+    Ok(response
+        .get("EvaluationResults")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|result| {
+            Some((
+                result.str_field("EvalActionName")?,
+                result.str_field("EvalDecision")?,
+            ))
+        })
+        .collect())
+}
+
+// The IAM entity behind a caller ARN; assumed roles are simulated as their
+// role (which loses any role path)
+fn policy_source(principal: &str) -> Option<String> {
+    match principal.split_once(":assumed-role/") {
+        Some((prefix, rest)) => {
+            let role = rest.split('/').next()?;
+            Some(format!(
+                "{}:role/{}",
+                prefix.replacen(":sts:", ":iam:", 1),
+                role
+            ))
+        }
+        None => principal.contains(":iam:").then(|| principal.to_string()),
+    }
+}
+
+// The task role of a task definition.
+pub async fn task_role(client: &Client, task_definition: &str) -> Result<Option<String>> {
+    ratelimit::acquire().await;
+    let described = client
+        .describe_task_definition()
+        .task_definition(task_definition)
+        .send()
+        .await?;
+    Ok(described
+        .task_definition
+        .and_then(|definition| definition.task_role_arn))
+}
+
+// Whether the task has a task role, which the exec agent uses to open its
+// ssmmessages channels.
+pub fn task_role_findings(task_role: Option<&str>) -> Vec<Finding> {
+    match task_role {
+        Some(role) => vec![Finding::new(Level::Ok, format!("task role {}", role))],
+        None => vec![Finding::new(
+            Level::Fail,
+            "the task definition has no task role; the exec agent can't open the SSM channel",
+        )
+        .with_fix("set taskRoleArn to a role allowing ssmmessages:CreateControlChannel, CreateDataChannel, OpenControlChannel and OpenDataChannel")],
+    }
+}
+
+// The subnet a task's traffic leaves from: its ENI's in awsvpc mode, the
+// container instance's otherwise (bridge and host networking).
+pub async fn task_subnet(
+    client: &Client,
+    cli: &AwsCli<'_>,
+    cluster_arn: &str,
+    task: &Task,
+) -> Result<Option<String>> {
+    let eni_subnet = task
+        .attachments
+        .iter()
+        .flatten()
+        .flat_map(|attachment| attachment.details.iter().flatten())
+        .find(|detail| detail.name.as_deref() == Some("subnetId"))
+        .and_then(|detail| detail.value.clone());
+    if eni_subnet.is_some() {
+        return Ok(eni_subnet);
+    }
+    let Some(container_instance) = task.container_instance_arn.as_deref() else {
+        return Ok(None);
+    };
+    ratelimit::acquire().await;
+    let described = client
+        .describe_container_instances()
+        .cluster(cluster_arn)
+        .container_instances(container_instance)
+        .send()
+        .await?;
+    let Some(instance_id) = described
+        .container_instances
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .and_then(|instance| instance.ec2_instance_id)
+    else {
+        return Ok(None);
+    };
+    let response = cli
+        .call(
+            "ec2",
+            "describe-instances",
+            &["--instance-ids".to_string(), instance_id],
+        )
+        .await?;
+    Ok(response
+        .get("Reservations")
+        .and_then(Value::as_array)
+        .and_then(|reservations| reservations.first())
+        .and_then(|reservation| reservation.get("Instances")?.as_array()?.first().cloned())
+        .and_then(|instance| instance.str_field("SubnetId")))
+}
+
+// Endpoints the agent talks to; only ssmmessages is strictly needed for
+// exec, the other two for the SSM agent at large
+const SSM_ENDPOINTS: &[(&str, Level)] = &[
+    ("ssmmessages", Level::Fail),
+    ("ssm", Level::Warn),
+    ("ec2messages", Level::Warn),
+];
+
+// Whether traffic from `subnet` can reach SSM: through a default route
+// (internet or NAT gateway, or anything else that forwards), or else
+// through interface endpoints in the VPC that serve the subnet's AZ.
+pub async fn ssm_route_findings(cli: &AwsCli<'_>, subnet: &str, scope: &Scope<'_>) -> Vec<Finding> {
+    let Some(info) = subnet_info(cli, subnet).await else {
+        return vec![Finding::new(
+            Level::Warn,
+            format!("cannot look up subnet {}", subnet),
+        )];
+    };
+    match default_route(cli, subnet, &info.vpc).await {
+        Ok(Some(target)) if target.starts_with("igw-") => {
+            return vec![Finding::new(
+                Level::Ok,
+                format!(
+                    "subnet {} routes to the internet gateway {}; the task needs a public IP",
+                    subnet, target
+                ),
+            )]
+        }
+        Ok(Some(target)) => {
+            return vec![Finding::new(
+                Level::Ok,
+                format!("subnet {} has a default route via {}", subnet, target),
+            )]
+        }
+        Ok(None) => {}
+        Err(err) => {
+            return vec![Finding::new(
+                Level::Warn,
+                format!(
+                    "cannot read the route table of subnet {}: {:#}",
+                    subnet, err
+                ),
+            )]
+        }
+    }
+
+    let mut findings = vec![Finding::new(
+        Level::Info,
+        format!(
+            "subnet {} ({}) has no default route, so SSM must be reached through VPC endpoints",
+            subnet, info.zone
+        ),
+    )];
+    let endpoints = match vpc_endpoints(cli, &info.vpc, scope.region).await {
+        Ok(endpoints) => endpoints,
+        Err(err) => {
+            findings.push(Finding::new(
+                Level::Warn,
+                format!("cannot list the VPC endpoints of {}: {:#}", info.vpc, err),
+            ));
+            return findings;
+        }
+    };
+    for (name, level) in SSM_ENDPOINTS {
+        let service = format!("com.amazonaws.{}.{}", scope.region, name);
+        let create = scope.aws(&format!(
+            "ec2 create-vpc-endpoint --vpc-id {} --vpc-endpoint-type Interface --service-name {} --subnet-ids {} --private-dns-enabled",
+            info.vpc, service, subnet
+        ));
+        let Some(endpoint) = endpoints.iter().find(|e| e.service == service) else {
+            findings.push(
+                Finding::new(*level, format!("{} has no {} endpoint", info.vpc, service))
+                    .with_fix(create),
+            );
+            continue;
+        };
+        let endpoint_id = &endpoint.id;
+        let mut serves_zone = false;
+        for endpoint_subnet in &endpoint.subnets {
+            if endpoint_subnet == subnet
+                || subnet_info(cli, endpoint_subnet)
+                    .await
+                    .is_some_and(|other| other.zone == info.zone)
+            {
+                serves_zone = true;
+                break;
+            }
+        }
+        if serves_zone {
+            findings.push(Finding::new(
+                Level::Ok,
+                format!("{} endpoint serves {}", service, info.zone),
+            ));
+        } else {
+            findings.push(
+                Finding::new(
+                    *level,
+                    format!("the {} endpoint has no subnet in {}", service, info.zone),
+                )
+                .with_fix(scope.aws(&format!(
+                    "ec2 modify-vpc-endpoint --vpc-endpoint-id {} --add-subnet-ids {}",
+                    endpoint_id, subnet
+                ))),
+            );
+        }
+    }
+    findings
+}
+
+struct SubnetInfo {
+    vpc: String,
+    zone: String,
+}
+
+async fn subnet_info(cli: &AwsCli<'_>, subnet: &str) -> Option<SubnetInfo> {
+    let response = cli
+        .call(
+            "ec2",
+            "describe-subnets",
+            &["--subnet-ids".to_string(), subnet.to_string()],
+        )
+        .await
+        .ok()?;
+    let subnet = response.get("Subnets")?.as_array()?.first()?.clone();
+    Some(SubnetInfo {
+        vpc: subnet.str_field("VpcId")?,
+        zone: subnet.str_field("AvailabilityZone")?,
+    })
+}
+
+// The target of the subnet's 0.0.0.0/0 route, from its own route table or
+// the VPC's main one when it has no association
+async fn default_route(cli: &AwsCli<'_>, subnet: &str, vpc: &str) -> Result<Option<String>> {
+    let table = match route_table(
+        cli,
+        vec![format!("Name=association.subnet-id,Values={}", subnet)],
+    )
+    .await?
+    {
+        Some(table) => table,
+        None => match route_table(
+            cli,
+            vec![
+                format!("Name=vpc-id,Values={}", vpc),
+                "Name=association.main,Values=true".to_string(),
+            ],
+        )
+        .await?
+        {
+            Some(table) => table,
+            None => return Ok(None),
+        },
+    };
+    Ok(table
+        .get("Routes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|route| route.str_field("DestinationCidrBlock").as_deref() == Some("0.0.0.0/0"))
+        .filter(|route| route.str_field("State").as_deref() != Some("blackhole"))
+        .find_map(|route| {
+            [
+                "GatewayId",
+                "NatGatewayId",
+                "TransitGatewayId",
+                "NetworkInterfaceId",
+                "InstanceId",
+                "VpcPeeringConnectionId",
+            ]
+            .iter()
+            .find_map(|key| route.str_field(key))
+        }))
+}
+
+// The first route table matching `filters`
+async fn route_table(cli: &AwsCli<'_>, filters: Vec<String>) -> Result<Option<Value>> {
+    let mut call_args = vec!["--filters".to_string()];
+    call_args.extend(filters);
+    let response = cli.call("ec2", "describe-route-tables", &call_args).await?;
+    Ok(response
+        .get("RouteTables")
+        .and_then(Value::as_array)
+        .and_then(|tables| tables.first().cloned()))
+}
+
+struct Endpoint {
+    id: String,
+    service: String,
+    subnets: Vec<String>,
+}
+
+// Endpoints of the region's SSM services in `vpc`
+async fn vpc_endpoints(cli: &AwsCli<'_>, vpc: &str, region: &str) -> Result<Vec<Endpoint>> {
+    let services: Vec<String> = SSM_ENDPOINTS
+        .iter()
+        .map(|(name, _)| format!("com.amazonaws.{}.{}", region, name))
+        .collect();
+    let response = cli
+        .call(
+            "ec2",
+            "describe-vpc-endpoints",
+            &[
+                "--filters".to_string(),
+                format!("Name=vpc-id,Values={}", vpc),
+                format!("Name=service-name,Values={}", services.join(",")),
+            ],
+        )
+        .await?;
+    Ok(response
+        .get("VpcEndpoints")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|endpoint| endpoint.str_field("State").as_deref() != Some("deleted"))
+        .filter_map(|endpoint| {
+            let subnets = endpoint
+                .get("SubnetIds")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect();
+            Some(Endpoint {
+                id: endpoint.str_field("VpcEndpointId")?,
+                service: endpoint.str_field("ServiceName")?,
+                subnets,
+            })
+        })
+        .collect())
+}
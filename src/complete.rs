@@ -0,0 +1,245 @@
+//! Shell completion: static scripts plus dynamic cluster and service names.
+
+use crate::cache::TopologyCache;
+use crate::{build_client, list_clusters, list_services, matching_clusters, natural, Args};
+use anyhow::Result;
+use clap::{CommandFactory, ValueEnum};
+use std::time::Duration;
+
+const COLD_FETCH_LIMIT: Duration = Duration::from_secs(2);
+
+// Cached names are offered up to this old
+const CACHE_MAX_AGE: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Complete {
+    /// Cluster names, for --cluster
+    Cluster,
+    /// Service names of --cluster, for --service
+    Service,
+    /// `cluster/` or `cluster/service`, for the positional target
+    Target,
+}
+
+// Print the names starting with `prefix`, one per line.
+pub async fn run(args: &Args, what: Complete, prefix: &str) -> Result<()> {
+    let names = tokio::time::timeout(COLD_FETCH_LIMIT, names(args, what, prefix))
+        .await
+        .unwrap_or_default();
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+async fn names(args: &Args, what: Complete, prefix: &str) -> Vec<String> {
+    let client = build_client(&args.profile, args.region.as_deref()).await;
+    let region = client.config().region().map(|r| r.to_string());
+    let cache = TopologyCache::new(&args.profile, region.as_deref(), CACHE_MAX_AGE);
+    let clusters = match cache.clusters() {
+        Some(clusters) => clusters,
+        None => match list_clusters(&client, None, None).await {
+            Ok(clusters) => {
+                cache.store_clusters(&clusters);
+                clusters
+            }
+            Err(_) => return Vec::new(),
+        },
+    };
+    let (cluster, service_prefix) = match what {
+        Complete::Cluster => {
+            return sorted(clusters.into_iter().map(|c| c.cluster_name), prefix);
+        }
+        Complete::Service => match args.cluster.as_deref() {
+            Some(cluster) => (cluster, prefix),
+            None => return Vec::new(),
+        },
+        Complete::Target => match prefix.split_once('/') {
+            Some(split) => split,
+            None => {
+                let names = clusters.into_iter().map(|c| format!("{}/", c.cluster_name));
+                return sorted(names, prefix);
+            }
+        },
+    };
+    let [cluster] = matching_clusters(&clusters, cluster)[..] else {
+        return Vec::new();
+    };
+    let services = match cache.services(&cluster.arn) {
+        Some(services) => services,
+        None => match list_services(&client, &cluster.arn, None, None).await {
+            Ok(services) => {
+                cache.store_services(&cluster.arn, &services);
+                services
+            }
+            Err(_) => return Vec::new(),
+        },
+    };
+    let names = services.into_iter().map(|s| s.service_name);
+    match what {
+        Complete::Target => {
+            let names = names.map(|name| format!("{}/{}", cluster.cluster_name, name));
+            sorted(
+                names,
+                &format!("{}/{}", cluster.cluster_name, service_prefix),
+            )
+        }
+        _ => sorted(names, service_prefix),
+    }
+}
+
+fn sorted(names: impl Iterator<Item = String>, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = names.filter(|name| name.starts_with(prefix)).collect();
+    names.sort_by(|a, b| natural::cmp(a, b));
+    names
+}
+
+// The completion script for `shell`.
+pub fn script(shell: Shell) -> String {
+    let command = Args::command();
+    let subcommands: Vec<&str> = command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set())
+        .map(|sub| sub.get_name())
+        .collect();
+    let flags: Vec<(String, String)> = command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .flat_map(|arg| {
+            let help = arg
+                .get_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default();
+            let help = help.lines().next().unwrap_or_default().to_string();
+            let long = arg
+                .get_long()
+                .map(|long| (format!("--{}", long), help.clone()));
+            let short = arg.get_short().map(|short| (format!("-{}", short), help));
+            long.into_iter().chain(short)
+        })
+        .collect();
+    match shell {
+        Shell::Bash => BASH
+            .replace("@SUBCOMMANDS@", &subcommands.join(" "))
+            .replace("@FLAGS@", &words(&flags)),
+        Shell::Zsh => ZSH
+            .replace("@SUBCOMMANDS@", &subcommands.join(" "))
+            .replace("@FLAGS@", &words(&flags)),
+        Shell::Fish => {
+            let mut script = FISH.replace("@SUBCOMMANDS@", &subcommands.join(" "));
+            for (flag, help) in &flags {
+                let option = match flag.strip_prefix("--") {
+                    Some(long) => format!("-l {}", long),
+                    None => format!("-s {}", &flag[1..]),
+                };
+                script.push_str(&format!(
+                    "complete -c ecs_remote {} -d '{}'\n",
+                    option,
+                    help.replace('\\', "\\\\").replace('\'', "\\'")
+                ));
+            }
+            script
+        }
+    }
+}
+
+fn words(flags: &[(String, String)]) -> String {
+    let flags: Vec<&str> = flags.iter().map(|(flag, _)| flag.as_str()).collect();
+    flags.join(" ")
+}
+
+// The flags that scope the lookup are passed on to `__complete`
+const BASH: &str = r#"# ecs_remote completion for bash; source it from ~/.bashrc:
+#   source <(ecs_remote completions bash)
+_ecs_remote() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local scope=() i
+    for ((i = 1; i < COMP_CWORD - 1; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -p|--profile|--region|-l|--cluster) scope+=("${COMP_WORDS[i]}" "${COMP_WORDS[i+1]}") ;;
+        esac
+    done
+    local IFS=$'\n'
+    case "$prev" in
+        -l|--cluster)
+            COMPREPLY=($(ecs_remote "${scope[@]}" __complete cluster "$cur" 2>/dev/null))
+            return ;;
+        -s|--service)
+            COMPREPLY=($(ecs_remote "${scope[@]}" __complete service "$cur" 2>/dev/null))
+            return ;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(IFS=' ' compgen -W "@FLAGS@" -- "$cur"))
+        return
+    fi
+    COMPREPLY=($(IFS=' ' compgen -W "@SUBCOMMANDS@" -- "$cur")
+        $(ecs_remote "${scope[@]}" __complete target "$cur" 2>/dev/null))
+    if [[ ${#COMPREPLY[@]} -eq 1 && "${COMPREPLY[0]}" == */ ]]; then
+        compopt -o nospace
+    fi
+}
+complete -F _ecs_remote ecs_remote
+"#;
+
+const ZSH: &str = r#"#compdef ecs_remote
+# ecs_remote completion for zsh; save as _ecs_remote in your $fpath, or:
+#   source <(ecs_remote completions zsh)
+_ecs_remote() {
+    local -a scope names
+    local i
+    for ((i = 2; i < CURRENT - 1; i++)); do
+        case ${words[i]} in
+            -p|--profile|--region|-l|--cluster) scope+=(${words[i]} ${words[i+1]}) ;;
+        esac
+    done
+    case ${words[CURRENT-1]} in
+        -l|--cluster)
+            names=(${(f)"$(ecs_remote $scope __complete cluster ${words[CURRENT]} 2>/dev/null)"})
+            compadd -a names
+            return ;;
+        -s|--service)
+            names=(${(f)"$(ecs_remote $scope __complete service ${words[CURRENT]} 2>/dev/null)"})
+            compadd -a names
+            return ;;
+    esac
+    if [[ ${words[CURRENT]} == -* ]]; then
+        compadd -- @FLAGS@
+        return
+    fi
+    compadd -- @SUBCOMMANDS@
+    names=(${(f)"$(ecs_remote $scope __complete target ${words[CURRENT]} 2>/dev/null)"})
+    compadd -S '' -a names
+}
+if [[ $zsh_eval_context[-1] == loadautofunc ]]; then
+    _ecs_remote "$@"
+else
+    compdef _ecs_remote ecs_remote
+fi
+"#;
+
+const FISH: &str = r#"# ecs_remote completion for fish; save it as
+# ~/.config/fish/completions/ecs_remote.fish, or:
+#   ecs_remote completions fish | source
+function __ecs_remote_scope
+    set -l tokens (commandline -opc)
+    for i in (seq 2 (math (count $tokens) - 1))
+        switch $tokens[$i]
+            case -p --profile --region -l --cluster
+                printf '%s\n' $tokens[$i] $tokens[(math $i + 1)]
+        end
+    end
+end
+complete -c ecs_remote -f
+complete -c ecs_remote -n __fish_use_subcommand -a '@SUBCOMMANDS@'
+complete -c ecs_remote -n __fish_use_subcommand -a '(ecs_remote (__ecs_remote_scope) __complete target (commandline -ct) 2>/dev/null)'
+complete -c ecs_remote -s l -l cluster -x -a '(ecs_remote (__ecs_remote_scope) __complete cluster (commandline -ct) 2>/dev/null)'
+complete -c ecs_remote -s s -l service -x -a '(ecs_remote (__ecs_remote_scope) __complete service (commandline -ct) 2>/dev/null)'
+"#;
@@ -0,0 +1,475 @@
+//! User configuration from `config.toml` (a subset of TOML).
+
+use crate::json::Value;
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+
+// Every key the file understands, for the man page.
+pub const KEYS: &[(&str, &str)] = &[
+    ("check_update", "Check GitHub for a newer release after each run (boolean)."),
+    (
+        "notify",
+        "Ring the bell and notify the desktop when a wait ends, like --notify (boolean).",
+    ),
+    ("plugin_path", "Where session-manager-plugin is installed."),
+    (
+        "ca_bundle",
+        "PEM bundle of the CAs to trust, like --ca-bundle.",
+    ),
+    (
+        "credential_command",
+        "Command printing credential_process JSON, like --credential-command; {profile} is replaced.",
+    ),
+    (
+        "api_rps",
+        "Most AWS API calls a second while listing and describing, like --api-rps.",
+    ),
+    (
+        "sort",
+        "Default order of the service and task pickers: name, age or status.",
+    ),
+    ("sort_reverse", "Reverse the picker order by default (boolean)."),
+    ("fips", "Use FIPS endpoints for every AWS call (boolean)."),
+    (
+        "dualstack",
+        "Use dual-stack (IPv6) endpoints for every AWS call (boolean).",
+    ),
+    (
+        "confirm",
+        "Ask before every session (true) or never (false); unset asks only for clusters matching confirm_pattern.",
+    ),
+    (
+        "confirm_pattern",
+        "Regex of cluster names to ask about before connecting (default \"prod\").",
+    ),
+    (
+        "[profile.NAME]",
+        "fips and dualstack for one AWS profile, over the top-level ones; region, used without --region; label, shown for the profile in the --profiles picker.",
+    ),
+    (
+        "[profile-group.NAME]",
+        "profiles, the list of AWS profiles --profile-group NAME searches like --profiles.",
+    ),
+    (
+        "[alias.NAME]",
+        "Connection defaults used with --alias NAME: profile, region, cluster, service, container, shell, workdir (strings) and login (boolean).",
+    ),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    root: Value,
+}
+
+// Connection defaults from an `[alias.NAME]` table (or a context); flags
+// always win over them.
+#[derive(Debug, Clone, Default)]
+pub struct Defaults {
+    pub profile: Option<String>,
+    pub region: Option<String>,
+    pub cluster: Option<String>,
+    pub service: Option<String>,
+    pub container: Option<String>,
+    pub shell: Option<String>,
+    pub login: Option<bool>,
+    pub workdir: Option<String>,
+}
+
+pub fn path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("ECS_REMOTE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME").or_else(|| std::env::var_os("APPDATA"))?)
+            .join(".config"),
+    };
+    Some(base.join("ecs_remote").join("config.toml"))
+}
+
+// Load the config file, treating a missing file as empty.
+pub fn load() -> Result<Config> {
+    let Some(path) = path() else {
+        return Ok(Config::default());
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => return Err(err).with_context(|| format!("Cannot read {}", path.display())),
+    };
+    let root =
+        parse(&contents).with_context(|| format!("Invalid config file {}", path.display()))?;
+    Ok(Config { root })
+}
+
+impl Config {
+    // Top-level `check_update = true` enables the release check.
+    pub fn check_update(&self) -> bool {
+        self.root
+            .get("check_update")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    // Top-level `plugin_path`, where session-manager-plugin is installed.
+    pub fn plugin_path(&self) -> Option<String> {
+        self.root.str_field("plugin_path")
+    }
+
+    // Top-level `notify = true` enables the end-of-wait notifications.
+    pub fn notify(&self) -> bool {
+        self.root
+            .get("notify")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    // Top-level `ca_bundle`, the default for `--ca-bundle`.
+    pub fn ca_bundle(&self) -> Option<String> {
+        self.root.str_field("ca_bundle")
+    }
+
+    // Top-level `credential_command`, the default for
+    // `--credential-command`.
+    pub fn credential_command(&self) -> Option<String> {
+        self.root.str_field("credential_command")
+    }
+
+    // Top-level `api_rps`, the default for `--api-rps`.
+    pub fn api_rps(&self) -> Option<f64> {
+        self.root.get("api_rps").and_then(Value::as_f64)
+    }
+
+    // Top-level `sort`, the default for `--sort`.
+    pub fn sort(&self) -> Option<String> {
+        self.root.str_field("sort")
+    }
+
+    // Top-level `sort_reverse = true` reverses the pickers like `--reverse`.
+    pub fn sort_reverse(&self) -> bool {
+        self.root
+            .get("sort_reverse")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    // Top-level `confirm`: always (true) or never (false) ask before
+    // connecting, instead of going by `confirm_pattern`.
+    pub fn confirm(&self) -> Option<bool> {
+        self.root.get("confirm").and_then(Value::as_bool)
+    }
+
+    // `fips` for `profile`, the default for `--fips`.
+    pub fn fips(&self, profile: &str) -> Option<bool> {
+        self.profile_flag(profile, "fips")
+    }
+
+    // `dualstack` for `profile`, the default for `--dualstack`.
+    pub fn dualstack(&self, profile: &str) -> Option<bool> {
+        self.profile_flag(profile, "dualstack")
+    }
+
+    // A boolean from `[profile.NAME]`, or else the top level
+    fn profile_flag(&self, profile: &str, key: &str) -> Option<bool> {
+        self.profile_table(profile)
+            .and_then(|table| table.get(key))
+            .or_else(|| self.root.get(key))
+            .and_then(Value::as_bool)
+    }
+
+    // `region` for `profile`, used when no `--region` is given.
+    pub fn profile_region(&self, profile: &str) -> Option<String> {
+        self.profile_table(profile)?.str_field("region")
+    }
+
+    // `label` for `profile`, shown instead of its name in pickers.
+    pub fn profile_label(&self, profile: &str) -> Option<String> {
+        self.profile_table(profile)?.str_field("label")
+    }
+
+    fn profile_table(&self, profile: &str) -> Option<&Value> {
+        self.root.get("profile")?.get(profile)
+    }
+
+    // Names of the defined profile groups, in file order.
+    pub fn profile_group_names(&self) -> Vec<String> {
+        match self.root.get("profile-group") {
+            Some(Value::Object(entries)) => entries.iter().map(|(name, _)| name.clone()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The profiles of the `[profile-group.NAME]` table.
+    pub fn profile_group(&self, name: &str) -> Result<Vec<String>> {
+        let table = self
+            .root
+            .get("profile-group")
+            .and_then(|groups| groups.get(name))
+            .ok_or_else(|| {
+                let names = self.profile_group_names();
+                if names.is_empty() {
+                    anyhow!("No profile group named '{}' (no groups are defined)", name)
+                } else {
+                    anyhow!(
+                        "No profile group named '{}'; defined: {}",
+                        name,
+                        names.join(", ")
+                    )
+                }
+            })?;
+        let profiles: Vec<String> = table
+            .get("profiles")
+            .and_then(Value::as_array)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if profiles.is_empty() {
+            return Err(anyhow!(
+                "Profile group '{}' needs a non-empty `profiles` list of strings",
+                name
+            ));
+        }
+        Ok(profiles)
+    }
+
+    // Top-level `confirm_pattern`, the clusters to ask about.
+    pub fn confirm_pattern(&self) -> Option<String> {
+        self.root.str_field("confirm_pattern")
+    }
+
+    // Names of the defined aliases, in file order.
+    pub fn alias_names(&self) -> Vec<String> {
+        match self.root.get("alias") {
+            Some(Value::Object(entries)) => entries.iter().map(|(name, _)| name.clone()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn alias(&self, name: &str) -> Result<Defaults> {
+        let table = self
+            .root
+            .get("alias")
+            .and_then(|a| a.get(name))
+            .ok_or_else(|| {
+                let names = self.alias_names();
+                if names.is_empty() {
+                    anyhow!("No alias named '{}' (no aliases are defined)", name)
+                } else {
+                    anyhow!("No alias named '{}'; defined: {}", name, names.join(", "))
+                }
+            })?;
+        Ok(Defaults {
+            profile: table.str_field("profile"),
+            region: table.str_field("region"),
+            cluster: table.str_field("cluster"),
+            service: table.str_field("service"),
+            container: table.str_field("container"),
+            shell: table.str_field("shell"),
+            login: table.get("login").and_then(Value::as_bool),
+            workdir: table.str_field("workdir"),
+        })
+    }
+}
+
+// Parse the supported TOML subset into nested objects.
+pub fn parse(input: &str) -> Result<Value> {
+    let mut root = Value::object();
+    let mut table: Vec<String> = Vec::new();
+    let mut lines = input.lines().enumerate();
+
+    while let Some((number, line)) = lines.next() {
+        let line = strip_comment(line);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let at = |err: anyhow::Error| anyhow!("line {}: {}", number + 1, err);
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| at(anyhow!("unterminated table header")))?;
+            table = parse_key(header).map_err(at)?;
+            table_mut(&mut root, &table).map_err(at)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| at(anyhow!("expected `key = value`")))?;
+        let key = parse_key(key).map_err(at)?;
+        let mut value = value.trim().to_string();
+        // Arrays may span lines until the closing bracket
+        while value.starts_with('[') && !array_closed(&value) {
+            let (_, next) = lines
+                .next()
+                .ok_or_else(|| at(anyhow!("unterminated array")))?;
+            value.push(' ');
+            value.push_str(strip_comment(next).trim());
+        }
+        let (parsed, rest) = parse_value(&value).map_err(at)?;
+        if !rest.trim().is_empty() {
+            return Err(at(anyhow!("unexpected text after value: {}", rest.trim())));
+        }
+
+        let (last, parents) = key.split_last().expect("keys are never empty");
+        let mut path = table.clone();
+        path.extend(parents.iter().cloned());
+        let target = table_mut(&mut root, &path).map_err(at)?;
+        if target.get(last).is_some() {
+            return Err(at(anyhow!("duplicate key '{}'", last)));
+        }
+        target.set(last, parsed);
+    }
+    Ok(root)
+}
+
+// Walk to (creating as needed) the table at `path`
+fn table_mut<'a>(root: &'a mut Value, path: &[String]) -> Result<&'a mut Value> {
+    let mut current = root;
+    for key in path {
+        if current.get(key).is_none() {
+            current.set(key, Value::object());
+        }
+        let Value::Object(entries) = current else {
+            unreachable!("tables are objects");
+        };
+        let (_, child) = entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .expect("just inserted");
+        if !matches!(child, Value::Object(_)) {
+            return Err(anyhow!("'{}' is not a table", key));
+        }
+        current = child;
+    }
+    Ok(current)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+fn array_closed(value: &str) -> bool {
+    let mut depth = 0;
+    let mut quote = None;
+    for c in value.chars() {
+        match (quote, c) {
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    depth <= 0
+}
+
+// A bare, quoted or dotted key
+fn parse_key(key: &str) -> Result<Vec<String>> {
+    let mut parts = Vec::new();
+    let mut rest = key.trim();
+    loop {
+        let (part, after) = if rest.starts_with('"') || rest.starts_with('\'') {
+            let (value, after) = parse_value(rest)?;
+            (value.as_str().unwrap_or_default().to_string(), after)
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(anyhow!("invalid key '{}'", key.trim()));
+            }
+            (rest[..end].to_string(), &rest[end..])
+        };
+        parts.push(part);
+        let after = after.trim_start();
+        match after.strip_prefix('.') {
+            Some(next) => rest = next.trim_start(),
+            None if after.is_empty() => return Ok(parts),
+            None => return Err(anyhow!("invalid key '{}'", key.trim())),
+        }
+    }
+}
+
+// Parse one value and return it with the unparsed remainder
+fn parse_value(input: &str) -> Result<(Value, &str)> {
+    let input = input.trim_start();
+    if let Some(rest) = input.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(out), &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some(other) => return Err(anyhow!("unsupported escape \\{}", other)),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        return Err(anyhow!("unterminated string"));
+    }
+    if let Some(rest) = input.strip_prefix('\'') {
+        let end = rest
+            .find('\'')
+            .ok_or_else(|| anyhow!("unterminated string"))?;
+        return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+    }
+    if let Some(mut rest) = input.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_value(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err(anyhow!("expected ',' or ']' in array"));
+            }
+        }
+    }
+
+    let end = input
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(input.len());
+    let (word, rest) = input.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => {
+            let number = word.replace('_', "");
+            match number.parse::<i64>() {
+                Ok(n) => Value::from(n),
+                Err(_) => Value::Number(
+                    number
+                        .parse::<f64>()
+                        .map_err(|_| anyhow!("invalid value '{}'", word))?,
+                ),
+            }
+        }
+    };
+    Ok((value, rest))
+}
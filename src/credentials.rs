@@ -0,0 +1,121 @@
+//! Credentials minted by a user-supplied command (`--credential-command`).
+
+use crate::failure::Kind;
+use crate::json;
+use anyhow::{anyhow, Result};
+use aws_sdk_ecs::config::Credentials;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+static MINTED: OnceLock<Minted> = OnceLock::new();
+
+static PROFILE_REQUESTED: AtomicBool = AtomicBool::new(true);
+
+struct Minted {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+    expiration: Option<String>,
+}
+
+// Run `command` for `profile` and use what it prints for the rest of the
+// run. Returns when the credentials expire, if the command said.
+pub fn mint(command: &str, profile: &str) -> Result<Option<String>> {
+    let words = shell_words::split(command)
+        .map_err(|err| Kind::Usage.error(format!("Invalid --credential-command: {}", err)))?;
+    let words: Vec<String> = words
+        .iter()
+        .map(|word| word.replace("{profile}", profile))
+        .collect();
+    let Some((program, rest)) = words.split_first() else {
+        return Err(Kind::Usage.error("--credential-command is empty"));
+    };
+    // The helper may need to ask for an MFA code, so only stdout is taken
+    let output = Command::new(program)
+        .args(rest)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|err| anyhow!("Cannot run credential command '{}': {}", program, err))?;
+    if !output.status.success() {
+        return Err(Kind::Denied.error(format!(
+            "Credential command '{}' failed ({})",
+            program, output.status
+        )));
+    }
+
+    let value = json::parse(&String::from_utf8_lossy(&output.stdout)).map_err(|err| {
+        anyhow!(
+            "Credential command '{}' printed invalid JSON: {}",
+            program,
+            err
+        )
+    })?;
+    let version = value.get("Version").and_then(json::Value::as_i64);
+    if version != Some(1) {
+        return Err(anyhow!(
+            "Credential command '{}' must print credential_process JSON with \"Version\": 1",
+            program
+        ));
+    }
+    let field = |name: &str| {
+        value
+            .str_field(name)
+            .ok_or_else(|| anyhow!("Credential command '{}' printed no {}", program, name))
+    };
+    let minted = Minted {
+        access_key_id: field("AccessKeyId")?,
+        secret_access_key: field("SecretAccessKey")?,
+        session_token: value.str_field("SessionToken"),
+        expiration: value.str_field("Expiration"),
+    };
+    let expiration = minted.expiration.clone();
+    let _ = MINTED.set(minted);
+    Ok(expiration)
+}
+
+// The minted credentials for an SDK client, if a command supplied them.
+pub fn sdk() -> Option<Credentials> {
+    let minted = MINTED.get()?;
+    Some(Credentials::new(
+        &minted.access_key_id,
+        &minted.secret_access_key,
+        minted.session_token.clone(),
+        None,
+        "credential-command",
+    ))
+}
+
+// Whether a profile was asked for; when not, the `default` profile is left
+// to the default chain.
+pub fn set_profile_requested(requested: bool) {
+    PROFILE_REQUESTED.store(requested, Ordering::Relaxed);
+}
+
+// The profile to name to the SDK and the AWS CLI: None for the `default`
+// profile nobody asked for.
+pub fn named(profile: &str) -> Option<&str> {
+    let implicit = profile == "default" && !PROFILE_REQUESTED.load(Ordering::Relaxed);
+    (!implicit).then_some(profile)
+}
+
+// Point an AWS CLI process at the credentials: the minted ones through the
+// environment, or else `--profile` when there is one to name.
+pub fn apply(cli: &mut Command, profile: &str) {
+    let Some(minted) = MINTED.get() else {
+        if let Some(profile) = named(profile) {
+            cli.args(["--profile", profile]);
+        }
+        return;
+    };
+    cli.env("AWS_ACCESS_KEY_ID", &minted.access_key_id)
+        .env("AWS_SECRET_ACCESS_KEY", &minted.secret_access_key)
+        // A profile in the environment would take precedence
+        .env_remove("AWS_PROFILE")
+        .env_remove("AWS_DEFAULT_PROFILE");
+    match &minted.session_token {
+        Some(token) => cli.env("AWS_SESSION_TOKEN", token),
+        None => cli.env_remove("AWS_SESSION_TOKEN"),
+    };
+}
@@ -0,0 +1,170 @@
+//! Guided diagnosis after a session failed to start.
+
+use crate::awscli::AwsCli;
+use crate::check::{self, Finding, Level, Scope};
+use crate::ratelimit;
+use crate::state::Target;
+use crate::{arn, load_sdk_config};
+use anyhow::Result;
+use aws_sdk_ecs::types::Task;
+use aws_sdk_ecs::Client;
+use console::style;
+
+const STEPS: usize = 9;
+
+// Numbers and prints the steps; `pass` is false once a step has failed
+struct Walk {
+    step: usize,
+}
+
+impl Walk {
+    fn pass(&mut self, title: &str, findings: Vec<Finding>) -> bool {
+        self.step += 1;
+        eprintln!(
+            "{}",
+            style(format!("{}/{} {}", self.step, STEPS, title)).bold()
+        );
+        for line in findings.iter().flat_map(Finding::lines) {
+            eprintln!("{}", line);
+        }
+        let failed = findings.iter().any(|f| f.level == Level::Fail);
+        if failed {
+            eprintln!("Stopped at the first failed step; fix it and connect again.");
+        }
+        !failed
+    }
+}
+
+// Walk the prerequisites of a session into `task_arn` at `target`.
+pub async fn run(target: &Target, task_arn: &str) -> Result<()> {
+    let sdk_config = load_sdk_config(&target.profile, target.region.as_deref()).await;
+    let client = Client::new(&sdk_config);
+    let region = sdk_config
+        .region()
+        .map(|region| region.to_string())
+        .or_else(|| arn::region(task_arn).map(str::to_string))
+        .unwrap_or_default();
+    let cli = AwsCli::new(&target.profile, Some(&region));
+    let scope = Scope {
+        profile: &target.profile,
+        region: &region,
+        cluster: arn::cluster_name(&target.cluster_arn),
+        service: &target.service_name,
+    };
+    eprintln!("Diagnosing task {}", arn::task_id(task_arn));
+    let mut walk = Walk { step: 0 };
+
+    ratelimit::acquire().await;
+    let task = client
+        .describe_tasks()
+        .cluster(&target.cluster_arn)
+        .tasks(task_arn)
+        .send()
+        .await?
+        .tasks
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        // A task that is gone is reported as not running
+        .unwrap_or_else(|| Task::builder().build());
+    if !walk.pass(
+        "Task still running",
+        check::task_running_findings(&task, &scope),
+    ) || !walk.pass(
+        "Execute command enabled",
+        check::exec_flag_findings(&task, &scope),
+    ) || !walk.pass(
+        "Exec agent",
+        check::agent_findings(&task, &target.container, &scope),
+    ) || !walk.pass("Platform version", check::platform_findings(&task, &scope))
+        || !walk.pass("Session Manager plugin", check::plugin_findings())
+    {
+        return Ok(());
+    }
+
+    let identity = aws_sdk_sts::Client::new(&sdk_config)
+        .get_caller_identity()
+        .send()
+        .await;
+    let (principal, findings) = match identity {
+        Ok(identity) => {
+            let principal = identity
+                .arn
+                .unwrap_or_else(|| format!("profile '{}'", target.profile));
+            let finding = Finding::new(Level::Ok, format!("calling as {}", principal));
+            (principal, vec![finding])
+        }
+        Err(err) => {
+            let finding = Finding::new(
+                Level::Fail,
+                format!(
+                    "cannot get the caller identity: {}",
+                    aws_sdk_sts::error::DisplayErrorContext(&err)
+                ),
+            )
+            .with_fix(format!("aws sso login --profile {}", target.profile));
+            (String::new(), vec![finding])
+        }
+    };
+    if !walk.pass("Caller identity", findings) {
+        return Ok(());
+    }
+
+    let exec_config = check::exec_config(&client, &target.cluster_arn).await?;
+    let mut findings = check::exec_config_findings(exec_config.as_ref(), &cli).await;
+    if let Some(key_id) = exec_config.as_ref().and_then(|c| c.kms_key_id.as_deref()) {
+        findings.extend(check::kms_findings(&cli, key_id, &principal).await);
+    }
+    if !walk.pass("Cluster exec configuration", findings)
+        || !walk.pass(
+            "Permission for ecs:ExecuteCommand",
+            check::execute_permission_findings(&cli, &principal, task_arn).await,
+        )
+    {
+        return Ok(());
+    }
+
+    let role = match task.task_definition_arn.as_deref() {
+        Some(definition) => check::task_role(&client, definition).await,
+        None => Ok(None),
+    };
+    let mut findings = match role {
+        Ok(role) => {
+            let mut findings = check::task_role_findings(role.as_deref());
+            if let Some(role) = role {
+                findings.extend(check::ssm_permission_findings(&cli, &role).await);
+            }
+            findings
+        }
+        Err(err) => vec![Finding::new(
+            Level::Warn,
+            format!("cannot describe the task definition: {:#}", err),
+        )],
+    };
+    if check::is_external(&task) {
+        // An ECS Anywhere task reaches SSM through its host's agent
+        let tasks = std::slice::from_ref(&task);
+        findings
+            .extend(check::external_host_findings(&client, &cli, &target.cluster_arn, tasks).await);
+    } else {
+        match check::task_subnet(&client, &cli, &target.cluster_arn, &task).await {
+            Ok(Some(subnet)) => {
+                findings.extend(check::ssm_route_findings(&cli, &subnet, &scope).await)
+            }
+            Ok(None) => findings.push(Finding::new(
+                Level::Info,
+                "cannot tell which subnet the task runs in; skipping the network checks",
+            )),
+            Err(err) => findings.push(Finding::new(
+                Level::Warn,
+                format!("cannot find the task's subnet: {:#}", err),
+            )),
+        }
+    }
+    if walk.pass("SSM reachability", findings) {
+        eprintln!(
+            "Every prerequisite checks out, so the failure may have been transient; try connecting again."
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Process exit codes, one per class of failure. Scripts rely on them, so
+//! the numbers are stable.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Other,
+    Usage,
+    NotFound,
+    NoExecReadyTasks,
+    Denied,
+    Session,
+    Deployment,
+    Incomplete,
+    Aborted,
+}
+
+// Error codes and messages the SDK, STS and the AWS CLI use for missing,
+// expired or insufficient credentials
+const DENIED_MARKERS: &[&str] = &[
+    "AccessDenied",
+    "UnauthorizedOperation",
+    "not authorized to perform",
+    "UnrecognizedClientException",
+    "InvalidClientTokenId",
+    "ExpiredToken",
+    "credential provider was not enabled",
+    "credentials provider was not properly configured",
+    "error occurred while loading credentials",
+    "Unable to locate credentials",
+];
+
+impl Kind {
+    pub const ALL: [Kind; 9] = [
+        Kind::Other,
+        Kind::Usage,
+        Kind::NotFound,
+        Kind::NoExecReadyTasks,
+        Kind::Denied,
+        Kind::Session,
+        Kind::Deployment,
+        Kind::Incomplete,
+        Kind::Aborted,
+    ];
+
+    pub fn code(self) -> u8 {
+        match self {
+            Kind::Other => 1,
+            Kind::Usage => 2,
+            Kind::NotFound => 3,
+            Kind::NoExecReadyTasks => 4,
+            Kind::Denied => 5,
+            Kind::Session => 6,
+            Kind::Deployment => 7,
+            Kind::Incomplete => 8,
+            Kind::Aborted => 130,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Other => "other error",
+            Kind::Usage => "usage error",
+            Kind::NotFound => "not found",
+            Kind::NoExecReadyTasks => "no exec-ready tasks",
+            Kind::Denied => "credentials or permissions",
+            Kind::Session => "session failed",
+            Kind::Deployment => "deployment failed",
+            Kind::Incomplete => "checks failed or sessions not terminated",
+            Kind::Aborted => "aborted",
+        }
+    }
+
+    // An error carrying `message` that exits with this kind's code.
+    pub fn error(self, message: impl Into<String>) -> anyhow::Error {
+        Failure {
+            kind: self,
+            message: message.into(),
+        }
+        .into()
+    }
+}
+
+#[derive(Debug)]
+struct Failure {
+    kind: Kind,
+    message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+// The class of `err`: the innermost tagged cause wins, then anything that
+// looks like a credential or permission failure.
+pub fn classify(err: &anyhow::Error) -> Kind {
+    if let Some(failure) = err
+        .chain()
+        .filter_map(|e| e.downcast_ref::<Failure>())
+        .last()
+    {
+        return failure.kind;
+    }
+    let denied = err.chain().any(|cause| {
+        let text = cause.to_string();
+        DENIED_MARKERS.iter().any(|marker| text.contains(marker))
+    });
+    if denied {
+        Kind::Denied
+    } else {
+        Kind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn each_kind_exits_with_its_code() {
+        let codes: Vec<(Kind, u8)> = Kind::ALL
+            .iter()
+            .map(|&kind| (kind, classify(&kind.error("boom")).code()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (Kind::Other, 1),
+                (Kind::Usage, 2),
+                (Kind::NotFound, 3),
+                (Kind::NoExecReadyTasks, 4),
+                (Kind::Denied, 5),
+                (Kind::Session, 6),
+                (Kind::Deployment, 7),
+                (Kind::Incomplete, 8),
+                (Kind::Aborted, 130),
+            ]
+        );
+    }
+
+    #[test]
+    fn untagged_errors_exit_with_the_generic_code() {
+        let err = anyhow::anyhow!("connection reset");
+        assert_eq!(classify(&err), Kind::Other);
+        assert_eq!(classify(&err).code(), 1);
+        let err = Err::<(), _>(std::io::Error::other("disk full"))
+            .context("writing history")
+            .unwrap_err();
+        assert_eq!(classify(&err).code(), 1);
+    }
+
+    #[test]
+    fn innermost_kind_wins_over_context() {
+        let err = Err::<(), _>(Kind::NotFound.error("no cluster uat"))
+            .context("listing tasks")
+            .unwrap_err();
+        assert_eq!(classify(&err), Kind::NotFound);
+        let err = Err::<(), _>(Kind::Usage.error("AccessDenied in the message"))
+            .context("User is not authorized to perform ecs:ListTasks")
+            .unwrap_err();
+        assert_eq!(classify(&err), Kind::Usage);
+    }
+
+    #[test]
+    fn credential_failures_are_denied() {
+        let err = anyhow::anyhow!("An error occurred (ExpiredTokenException)");
+        assert_eq!(classify(&err).code(), Kind::Denied.code());
+    }
+}
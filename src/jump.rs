@@ -0,0 +1,302 @@
+//! `jump`: one query across the clusters, services and task families of the
+//! profile.
+
+use crate::cache::TopologyCache;
+use crate::failure::Kind;
+use crate::picker::Picker;
+use crate::{
+    arn, build_client, cached_clusters, connect, list_services, natural, ratelimit, Args,
+    ClusterInfo, Session, DESCRIBE_SERVICES_BATCH,
+};
+use anyhow::Result;
+use aws_sdk_ecs::Client;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// Clusters whose services are listed at the same time
+const CONCURRENCY: usize = 4;
+
+// Match quality of one word against one name
+const EXACT: u32 = 100;
+const PREFIX: u32 = 75;
+const PART: u32 = 60;
+const SUBSTRING: u32 = 40;
+const SCATTERED: u32 = 10;
+
+// The searchable names of one cluster
+struct Listing {
+    cluster: ClusterInfo,
+    // (service name, task definition family)
+    services: Vec<(String, Option<String>)>,
+    from_cache: bool,
+}
+
+struct Candidate {
+    cluster_arn: String,
+    service_name: Option<String>,
+    label: String,
+    score: u32,
+}
+
+pub async fn run(args: &Args, query: &str) -> Result<()> {
+    let words: Vec<String> = query
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return Err(Kind::Usage.error("jump needs something to look for"));
+    }
+    let client = build_client(&args.profile, args.region.as_deref()).await;
+    let region = client.config().region().map(|r| r.to_string());
+    let cache = TopologyCache::new(&args.profile, region.as_deref(), args.cache_ttl);
+
+    let mut refresh = args.refresh;
+    let candidates = loop {
+        let listings = search(&client, &cache, args, refresh).await?;
+        let candidates = rank(&listings, &words);
+        let cached = listings.iter().any(|listing| listing.from_cache);
+        // The cached lists may predate what is looked for
+        if candidates.is_empty() && cached && !refresh {
+            refresh = true;
+            continue;
+        }
+        if candidates.is_empty() {
+            let services: usize = listings.iter().map(|l| l.services.len()).sum();
+            return Err(Kind::NotFound.error(format!(
+                "Nothing in profile {}{} matches '{}' ({} clusters, {} services searched)",
+                args.profile,
+                region
+                    .map(|r| format!(", region {}", r))
+                    .unwrap_or_default(),
+                query,
+                listings.len(),
+                services
+            )));
+        }
+        break candidates;
+    };
+
+    let choice = match &candidates[..] {
+        [only] => {
+            eprintln!("Jumping to {}", only.label);
+            only
+        }
+        _ => {
+            let labels: Vec<String> = candidates.iter().map(|c| c.label.clone()).collect();
+            &candidates[Picker::new("Jump to", &labels).interact()?]
+        }
+    };
+    let mut args = args.clone();
+    args.cluster = Some(choice.cluster_arn.clone());
+    args.service = choice.service_name.clone();
+    connect(&args, &Session::from_args(&args)?).await
+}
+
+// The clusters in scope with their services and families, listed a few
+// clusters at a time
+async fn search(
+    client: &Client,
+    cache: &TopologyCache,
+    args: &Args,
+    refresh: bool,
+) -> Result<Vec<Listing>> {
+    let (clusters, clusters_cached) = cached_clusters(client, cache, args, refresh).await?;
+    let limit = Arc::new(Semaphore::new(CONCURRENCY));
+    let mut lookups = JoinSet::new();
+    for (index, cluster) in clusters.into_iter().enumerate() {
+        let client = client.clone();
+        let cache = cache.clone();
+        let limit = limit.clone();
+        lookups.spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let listing = services(&client, &cache, &cluster, refresh).await;
+            (index, cluster, listing)
+        });
+    }
+
+    let mut listings = Vec::new();
+    let mut failed = Vec::new();
+    while let Some(done) = lookups.join_next().await {
+        let Ok((index, cluster, listing)) = done else {
+            continue;
+        };
+        match listing {
+            Ok((services, from_cache)) => listings.push((
+                index,
+                Listing {
+                    cluster,
+                    services,
+                    from_cache: from_cache || clusters_cached,
+                },
+            )),
+            Err(_) => failed.push(cluster.cluster_name),
+        }
+    }
+    if !failed.is_empty() {
+        failed.sort_by(|a, b| natural::cmp(a, b));
+        eprintln!(
+            "Warning: could not list the services of {}; not searched",
+            failed.join(", ")
+        );
+    }
+    listings.sort_by_key(|(index, _)| *index);
+    Ok(listings.into_iter().map(|(_, listing)| listing).collect())
+}
+
+// The services of one cluster with their families, and whether both came
+// from the cache
+async fn services(
+    client: &Client,
+    cache: &TopologyCache,
+    cluster: &ClusterInfo,
+    refresh: bool,
+) -> Result<(Vec<(String, Option<String>)>, bool)> {
+    let cached = (!refresh)
+        .then(|| {
+            cache
+                .services(&cluster.arn)
+                .zip(cache.families(&cluster.arn))
+        })
+        .flatten();
+    if let Some((services, families)) = cached {
+        let services = services
+            .into_iter()
+            .map(|service| {
+                let family = families
+                    .iter()
+                    .find(|(name, _)| *name == service.service_name)
+                    .map(|(_, family)| family.clone());
+                (service.service_name, family)
+            })
+            .collect();
+        return Ok((services, true));
+    }
+
+    ratelimit::acquire().await;
+    let services = list_services(client, &cluster.arn, None, None).await?;
+    cache.store_services(&cluster.arn, &services);
+    let names: Vec<String> = services.into_iter().map(|s| s.service_name).collect();
+    let mut families = Vec::new();
+    for chunk in names.chunks(DESCRIBE_SERVICES_BATCH) {
+        ratelimit::acquire().await;
+        // Without ecs:DescribeServices the names alone are searched
+        let Ok(described) = client
+            .describe_services()
+            .cluster(&cluster.arn)
+            .set_services(Some(chunk.to_vec()))
+            .send()
+            .await
+        else {
+            break;
+        };
+        families.extend(
+            described
+                .services
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|service| {
+                    let family = arn::task_family(service.task_definition.as_deref()?);
+                    Some((service.service_name?, family.to_string()))
+                }),
+        );
+    }
+    cache.store_families(&cluster.arn, &families);
+    let services = names
+        .into_iter()
+        .map(|name| {
+            let family = families
+                .iter()
+                .find(|(service, _)| *service == name)
+                .map(|(_, family)| family.clone());
+            (name, family)
+        })
+        .collect();
+    Ok((services, false))
+}
+
+// The candidates for the query, best first
+fn rank(listings: &[Listing], words: &[String]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    for listing in listings {
+        let cluster = &listing.cluster;
+        let cluster_scores: Vec<Option<u32>> = words
+            .iter()
+            .map(|word| score(word, &cluster.cluster_name))
+            .collect();
+        if let Some(total) = sum(&cluster_scores) {
+            candidates.push(Candidate {
+                cluster_arn: cluster.arn.clone(),
+                service_name: None,
+                label: format!("{} (pick a service)", cluster.cluster_name),
+                score: total,
+            });
+        }
+        for (service_name, family) in &listing.services {
+            let service_scores: Vec<Option<u32>> = words
+                .iter()
+                .map(|word| {
+                    let family = family.as_deref().and_then(|family| score(word, family));
+                    score(word, service_name).max(family)
+                })
+                .collect();
+            if service_scores.iter().all(Option::is_none) {
+                continue;
+            }
+            let best: Vec<Option<u32>> = service_scores
+                .iter()
+                .zip(&cluster_scores)
+                .map(|(service, cluster)| (*service).max(*cluster))
+                .collect();
+            let Some(total) = sum(&best) else {
+                continue;
+            };
+            let mut label = format!("{} / {}", cluster.cluster_name, service_name);
+            if let Some(family) = family.as_ref().filter(|family| *family != service_name) {
+                label.push_str(&format!("  (task {})", family));
+            }
+            candidates.push(Candidate {
+                cluster_arn: cluster.arn.clone(),
+                service_name: Some(service_name.clone()),
+                label,
+                score: total,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| natural::cmp(&a.label, &b.label))
+    });
+    candidates
+}
+
+// The total of the word scores, if every word matched
+fn sum(scores: &[Option<u32>]) -> Option<u32> {
+    scores.iter().copied().sum()
+}
+
+// How well `word` (lowercase) matches `name`, if at all
+fn score(word: &str, name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    if name == word {
+        return Some(EXACT);
+    }
+    if name.starts_with(word) {
+        return Some(PREFIX);
+    }
+    if name.contains(word) {
+        let part = name.match_indices(word).any(|(at, _)| {
+            name[..at]
+                .chars()
+                .next_back()
+                .is_some_and(|c| matches!(c, '-' | '_' | '.'))
+        });
+        return Some(if part { PART } else { SUBSTRING });
+    }
+    let mut letters = name.chars();
+    word.chars()
+        .all(|c| letters.any(|l| l == c))
+        .then_some(SCATTERED)
+}
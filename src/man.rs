@@ -0,0 +1,225 @@
+//! The man page, rendered as roff from the clap definitions.
+
+use crate::failure::Kind;
+use crate::{awscli, config, plugin, tls};
+use clap::{Arg, ArgAction, Command};
+use std::fmt::Write;
+
+// Variables read at startup, besides the AWS SDK's own
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        "ECS_REMOTE_CONFIG",
+        "Path of the config file, instead of $XDG_CONFIG_HOME/ecs_remote/config.toml.",
+    ),
+    (
+        "XDG_CONFIG_HOME",
+        "Base directory of the config file (default ~/.config).",
+    ),
+    (
+        "ECS_REMOTE_STATE_DIR",
+        "Where history, favorites, contexts and the topology cache are kept.",
+    ),
+    (
+        "XDG_STATE_HOME",
+        "Base directory of the state directory (default ~/.local/state).",
+    ),
+    (
+        plugin::ENV_VAR,
+        "session-manager-plugin binary or its directory, like --plugin-path.",
+    ),
+    (
+        awscli::ENV_VAR,
+        "AWS CLI executable to run, instead of aws (aws.exe or aws.cmd on Windows) from PATH.",
+    ),
+    (
+        "HTTPS_PROXY, HTTP_PROXY",
+        "HTTP proxy for the AWS calls, like --proxy-url.",
+    ),
+    ("NO_PROXY", "Hosts and domains reached without the proxy."),
+    (
+        tls::ENV_VAR,
+        "PEM bundle of the CAs to trust, like --ca-bundle.",
+    ),
+    (
+        "AWS_*",
+        "The usual AWS SDK and CLI variables, e.g. AWS_REGION and AWS_CONFIG_FILE.",
+    ),
+];
+
+// Render the whole page for `command`.
+pub fn render(command: &Command) -> String {
+    let name = command.get_name().to_string();
+    let mut page = String::new();
+    let _ = writeln!(
+        page,
+        ".TH {} 1 \"\" \"{} {}\"",
+        name.to_uppercase(),
+        name,
+        command.get_version().unwrap_or_default()
+    );
+
+    section(&mut page, "NAME");
+    line(
+        &mut page,
+        &format!(
+            "{} - {}",
+            name,
+            command
+                .get_about()
+                .map(|a| a.to_string())
+                .unwrap_or_default()
+        ),
+    );
+
+    section(&mut page, "SYNOPSIS");
+    let usage = command.clone().render_usage().to_string();
+    line(&mut page, usage.trim_start_matches("Usage: "));
+
+    section(&mut page, "OPTIONS");
+    arguments(&mut page, command, true);
+
+    section(&mut page, "COMMANDS");
+    subcommands(&mut page, command, &name);
+
+    section(&mut page, "ENVIRONMENT");
+    for (var, description) in ENVIRONMENT {
+        item(&mut page, &bold(var), description);
+    }
+
+    section(&mut page, "FILES");
+    item(
+        &mut page,
+        &italic("$XDG_CONFIG_HOME/ecs_remote/config.toml"),
+        "The config file, in a subset of TOML; a missing file is an empty configuration. Keys:",
+    );
+    for (key, description) in config::KEYS {
+        item(&mut page, &bold(key), description);
+    }
+    item(
+        &mut page,
+        &italic("$XDG_STATE_HOME/ecs_remote/"),
+        "Connection history, favorites, contexts and the cluster/service cache.",
+    );
+
+    section(&mut page, "EXIT STATUS");
+    item(&mut page, &bold("0"), "success");
+    for kind in Kind::ALL {
+        item(&mut page, &bold(&kind.code().to_string()), kind.label());
+    }
+
+    if let Some(examples) = command.get_after_help() {
+        section(&mut page, "EXAMPLES");
+        for example in examples.to_string().lines().skip(1) {
+            let _ = writeln!(page, ".br");
+            line(&mut page, example.trim());
+        }
+    }
+    page
+}
+
+// The visible arguments of `command` itself; globals are listed once, under
+// the top-level OPTIONS
+fn arguments(page: &mut String, command: &Command, top: bool) {
+    for arg in command.get_arguments() {
+        if arg.is_hide_set() || (arg.is_global_set() && !top) {
+            continue;
+        }
+        let help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+        item(page, &signature(arg), &with_details(arg, help));
+    }
+}
+
+fn subcommands(page: &mut String, command: &Command, prefix: &str) {
+    for sub in command.get_subcommands() {
+        if sub.is_hide_set() || sub.get_name() == "help" {
+            continue;
+        }
+        let name = format!("{} {}", prefix, sub.get_name());
+        let _ = writeln!(page, ".SS \"{}\"", escape(&name));
+        line(
+            page,
+            &sub.get_about().map(|a| a.to_string()).unwrap_or_default(),
+        );
+        arguments(page, sub, false);
+        subcommands(page, sub, &name);
+    }
+}
+
+// `-p, --profile PROFILE`, or `TARGET` for positionals
+fn signature(arg: &Arg) -> String {
+    let value = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().to_string().to_uppercase());
+    if arg.is_positional() {
+        return italic(&value);
+    }
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(bold(&format!("-{}", short)));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(bold(&format!("--{}", long)));
+    }
+    let mut signature = names.join(", ");
+    if matches!(arg.get_action(), ArgAction::Set | ArgAction::Append) {
+        signature.push(' ');
+        signature.push_str(&italic(&value));
+    }
+    signature
+}
+
+fn with_details(arg: &Arg, mut help: String) -> String {
+    let values: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !values.is_empty() && matches!(arg.get_action(), ArgAction::Set | ArgAction::Append) {
+        let _ = write!(help, " [possible values: {}]", values.join(", "));
+    }
+    let defaults: Vec<String> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+    if !defaults.is_empty() && !matches!(arg.get_action(), ArgAction::SetTrue) {
+        let _ = write!(help, " [default: {}]", defaults.join(","));
+    }
+    help
+}
+
+fn section(page: &mut String, title: &str) {
+    let _ = writeln!(page, ".SH {}", title);
+}
+
+fn item(page: &mut String, tag: &str, text: &str) {
+    let _ = writeln!(page, ".TP\n{}", tag);
+    if !text.is_empty() {
+        line(page, text);
+    }
+}
+
+fn line(page: &mut String, text: &str) {
+    let _ = writeln!(page, "{}", escape(text));
+}
+
+fn bold(text: &str) -> String {
+    format!("\\fB{}\\fR", escape(text))
+}
+
+fn italic(text: &str) -> String {
+    format!("\\fI{}\\fR", escape(text))
+}
+
+// Backslashes and dashes are special to roff, as is a leading dot or quote
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,145 @@
+//! Recent CPU and memory use of tasks, from Container Insights.
+
+use crate::awscli::AwsCli;
+use crate::json::Value;
+use anyhow::Result;
+use std::collections::HashMap;
+
+const NAMESPACE: &str = "ECS/ContainerInsights";
+
+// How far back to average
+const WINDOW_SECS: i64 = 300;
+const PERIOD_SECS: i64 = 60;
+
+// get-metric-data takes at most 500 queries per call, six per task
+const TASKS_PER_CALL: usize = 80;
+
+// Average CPU and memory use of a task, in percent of its reservation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub cpu: Option<f64>,
+    pub memory: Option<f64>,
+}
+
+impl Usage {
+    // `cpu 87% mem 52%`, with a dash for what has no datapoints.
+    pub fn label(&self) -> String {
+        let percent = |value: Option<f64>| match value {
+            Some(value) => format!("{:.0}%", value),
+            None => "–".to_string(),
+        };
+        format!("cpu {} mem {}", percent(self.cpu), percent(self.memory))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cpu.is_none() && self.memory.is_none()
+    }
+}
+
+// A task to look up: its ID and task definition family.
+pub struct Query<'a> {
+    pub task_id: &'a str,
+    pub family: &'a str,
+}
+
+// Usage per task ID over the last few minutes. Tasks without datapoints
+// get an empty `Usage`.
+pub async fn fetch(
+    cli: &AwsCli<'_>,
+    cluster: &str,
+    tasks: &[Query<'_>],
+) -> Result<HashMap<String, Usage>> {
+    let end = crate::state::now_secs();
+    let start = end - WINDOW_SECS;
+    let mut usage = HashMap::new();
+    for batch in tasks.chunks(TASKS_PER_CALL) {
+        let queries: Vec<Value> = batch
+            .iter()
+            .enumerate()
+            .flat_map(|(i, task)| task_queries(i, cluster, task))
+            .collect();
+        crate::ratelimit::acquire().await;
+        let response = cli
+            .call(
+                "cloudwatch",
+                "get-metric-data",
+                &[
+                    "--metric-data-queries".to_string(),
+                    Value::Array(queries).to_string(),
+                    "--start-time".to_string(),
+                    start.to_string(),
+                    "--end-time".to_string(),
+                    end.to_string(),
+                ],
+            )
+            .await?;
+        // The newest datapoint comes first
+        let latest: HashMap<String, f64> = response
+            .get("MetricDataResults")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|result| {
+                let value = result.get("Values")?.as_array()?.first()?.as_f64()?;
+                Some((result.str_field("Id")?, value))
+            })
+            .collect();
+        for (i, task) in batch.iter().enumerate() {
+            usage.insert(
+                task.task_id.to_string(),
+                Usage {
+                    cpu: latest.get(&format!("cpu{}", i)).copied(),
+                    memory: latest.get(&format!("mem{}", i)).copied(),
+                },
+            );
+        }
+    }
+    Ok(usage)
+}
+
+// Utilized and reserved CPU and memory of one task, and the percentages
+// computed from them (the only results returned)
+fn task_queries(i: usize, cluster: &str, task: &Query) -> Vec<Value> {
+    let metric = |id: String, name: &str| {
+        let dimensions: Vec<Value> = [
+            ("ClusterName", cluster),
+            ("TaskDefinitionFamily", task.family),
+            ("TaskId", task.task_id),
+        ]
+        .iter()
+        .map(|(name, value)| {
+            let mut dimension = Value::object();
+            dimension.set("Name", *name);
+            dimension.set("Value", *value);
+            dimension
+        })
+        .collect();
+        let mut metric = Value::object();
+        metric.set("Namespace", NAMESPACE);
+        metric.set("MetricName", name);
+        metric.set("Dimensions", dimensions);
+        let mut stat = Value::object();
+        stat.set("Metric", metric);
+        stat.set("Period", PERIOD_SECS);
+        stat.set("Stat", "Average");
+        let mut query = Value::object();
+        query.set("Id", id);
+        query.set("MetricStat", stat);
+        query.set("ReturnData", false);
+        query
+    };
+    let expression = |id: String, expression: String| {
+        let mut query = Value::object();
+        query.set("Id", id);
+        query.set("Expression", expression);
+        query
+    };
+    vec![
+        metric(format!("cu{}", i), "CpuUtilized"),
+        metric(format!("cr{}", i), "CpuReserved"),
+        metric(format!("mu{}", i), "MemoryUtilized"),
+        metric(format!("mr{}", i), "MemoryReserved"),
+        expression(format!("cpu{}", i), format!("100*cu{}/cr{}", i, i)),
+        expression(format!("mem{}", i), format!("100*mu{}/mr{}", i, i)),
+    ]
+}
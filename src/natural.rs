@@ -0,0 +1,153 @@
+//! Natural ordering for names, so `worker-2` comes before `worker-10`.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// Compare two names in natural order.
+pub fn cmp(a: &str, b: &str) -> Ordering {
+    let mut left = a.chars().peekable();
+    let mut right = b.chars().peekable();
+    // Settles names that are otherwise equal: fewer leading zeros first
+    let mut zeros = Ordering::Equal;
+    loop {
+        let (l, r) = match (left.peek(), right.peek()) {
+            (None, None) => return zeros.then_with(|| a.cmp(b)),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&l), Some(&r)) => (l, r),
+        };
+        if l.is_ascii_digit() && r.is_ascii_digit() {
+            let l = digits(&mut left);
+            let r = digits(&mut right);
+            let (l_value, r_value) = (l.trim_start_matches('0'), r.trim_start_matches('0'));
+            let order = l_value
+                .len()
+                .cmp(&r_value.len())
+                .then_with(|| l_value.cmp(r_value));
+            if order != Ordering::Equal {
+                return order;
+            }
+            if zeros == Ordering::Equal {
+                zeros = l.len().cmp(&r.len());
+            }
+            continue;
+        }
+        let order = l.to_lowercase().cmp(r.to_lowercase());
+        if order != Ordering::Equal {
+            return order;
+        }
+        left.next();
+        right.next();
+    }
+}
+
+// The run of ASCII digits at the front of `chars`
+fn digits(chars: &mut Peekable<Chars>) -> String {
+    let mut run = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        run.push(digit);
+    }
+    run
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(cmp("worker-2", "worker-10"), Ordering::Less);
+        assert_eq!(cmp("worker-10", "worker-2"), Ordering::Greater);
+        assert_eq!(cmp("worker-10", "worker-10"), Ordering::Equal);
+        assert_eq!(cmp("web-9-b", "web-10-a"), Ordering::Less);
+        assert_eq!(cmp("v1.2.10", "v1.10.2"), Ordering::Less);
+        assert_eq!(cmp("web", "web-1"), Ordering::Less);
+    }
+
+    #[test]
+    fn case_only_settles_ties() {
+        assert_eq!(cmp("Web-2", "web-10"), Ordering::Less);
+        assert_eq!(cmp("api", "Web"), Ordering::Less);
+        // Equal but for case: uppercase first, so the order is still total
+        assert_eq!(cmp("Web", "web"), Ordering::Less);
+        assert_eq!(cmp("web", "Web"), Ordering::Greater);
+    }
+
+    #[test]
+    fn fewer_leading_zeros_first() {
+        assert_eq!(cmp("web-1", "web-01"), Ordering::Less);
+        assert_eq!(cmp("web-01", "web-1"), Ordering::Greater);
+        assert_eq!(cmp("web-01", "web-2"), Ordering::Less);
+        assert_eq!(cmp("web-001", "web-01"), Ordering::Greater);
+        // Leading zeros only count when nothing else differs
+        assert_eq!(cmp("web-01-b", "web-1-a"), Ordering::Greater);
+        assert_eq!(cmp("web-0", "web-00"), Ordering::Less);
+    }
+
+    #[test]
+    fn digit_runs_longer_than_u64() {
+        let max = u64::MAX.to_string();
+        let big = "184467440737095516160";
+        assert_eq!(
+            cmp(&format!("job-{}", max), &format!("job-{}", big)),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp(
+                "job-100000000000000000000000",
+                "job-99999999999999999999999"
+            ),
+            Ordering::Greater
+        );
+        assert_eq!(
+            cmp(
+                "job-123456789012345678901234",
+                "job-123456789012345678901235"
+            ),
+            Ordering::Less
+        );
+        assert_eq!(
+            cmp(&format!("job-0{}", big), &format!("job-{}", big)),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn order_is_total_and_antisymmetric() {
+        let mut names = vec![
+            "worker-10",
+            "Worker-2",
+            "worker-2",
+            "worker-02",
+            "worker",
+            "worker-",
+            "web-01",
+            "web-1",
+            "Web-1",
+            "web-1a",
+            "web-1-a",
+            "a",
+            "A",
+            "",
+            "0",
+            "00",
+            "007",
+            "7",
+            "10",
+            "x99999999999999999999999",
+            "x100000000000000000000000",
+            "émile-3",
+            "emile-3",
+        ];
+        names.sort_by(|a, b| cmp(a, b));
+        for (i, a) in names.iter().enumerate() {
+            assert_eq!(cmp(a, a), Ordering::Equal, "{:?}", a);
+            for (j, b) in names.iter().enumerate() {
+                assert_eq!(cmp(a, b), cmp(b, a).reverse(), "{:?} {:?}", a, b);
+                // Sorted, so every pair is in order, which takes transitivity
+                assert_eq!(cmp(a, b), i.cmp(&j), "{:?} {:?}", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Bell and desktop notification when a long wait is over (`--notify`).
+
+use crate::picker;
+use console::Term;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+const TITLE: &str = "ecs_remote";
+
+// Notify at the end of waits when `enabled`, unless nobody can see it.
+// Call after `picker::set_interactive`.
+pub fn configure(enabled: bool) {
+    let watched =
+        picker::is_interactive() && Term::stderr().is_term() && std::env::var_os("CI").is_none();
+    ENABLED.store(enabled && watched, Ordering::Relaxed);
+}
+
+// Ring the bell and show `message` (the outcome of the wait) on the
+// desktop, when enabled.
+pub fn send(message: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut stderr = std::io::stderr();
+    let _ = stderr.write_all(b"\x07");
+    let _ = stderr.flush();
+    let _ = desktop(message);
+}
+
+// Hand the message to the platform's notifier, if it has one
+fn desktop(message: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            "display notification {} with title {}",
+            applescript(message),
+            applescript(TITLE)
+        ));
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", TITLE, TITLE, message]);
+        command
+    } else {
+        return Ok(());
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(drop)
+}
+
+// An AppleScript string literal
+fn applescript(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
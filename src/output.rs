@@ -0,0 +1,591 @@
+//! Rendering of listings as tables, JSON, YAML or CSV.
+
+use crate::json::Value;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    /// Table with the full ARNs and other identifiers, never truncated
+    Wide,
+    Json,
+    /// Same data as JSON; null fields are left out
+    Yaml,
+    /// RFC 4180 CSV with raw values (listings only)
+    Csv,
+}
+
+// A column of a `Listing`: its JSON key (also the `--fields` name), its
+// table/CSV header and what it holds.
+pub struct Column {
+    pub key: &'static str,
+    pub header: &'static str,
+    pub description: &'static str,
+    // Only shown by default with `--output wide`, json and yaml
+    pub wide: bool,
+}
+
+impl Column {
+    pub const fn new(key: &'static str, header: &'static str, description: &'static str) -> Self {
+        Column {
+            key,
+            header,
+            description,
+            wide: false,
+        }
+    }
+
+    // A column (full ARNs, runtime IDs) only shown by default with
+    // `--output wide`, json and yaml; `--fields` picks it in any format.
+    pub const fn wide(key: &'static str, header: &'static str, description: &'static str) -> Self {
+        Column {
+            wide: true,
+            ..Column::new(key, header, description)
+        }
+    }
+}
+
+// Print the columns `--fields` accepts for a listing.
+pub fn print_fields(columns: &[Column]) {
+    let mut table = Table::new(&["FIELD", "DESCRIPTION"]);
+    for column in columns {
+        table.push(vec![column.key.to_string(), column.description.to_string()]);
+    }
+    table.print();
+}
+
+// One value of a listing row: the raw value for JSON and CSV, and its
+// humanized form for the table.
+pub struct Cell {
+    pub raw: Value,
+    pub display: String,
+}
+
+impl Cell {
+    pub fn new(raw: impl Into<Value>, display: impl Into<String>) -> Self {
+        Cell {
+            raw: raw.into(),
+            display: display.into(),
+        }
+    }
+
+    // A plain string shown as-is.
+    pub fn text(value: impl Into<String>) -> Self {
+        let value = value.into();
+        Cell {
+            raw: Value::from(&value),
+            display: value,
+        }
+    }
+
+    // A missing value: `-` in the table, empty in CSV, omitted from JSON.
+    pub fn none() -> Self {
+        Cell {
+            raw: Value::Null,
+            display: "-".to_string(),
+        }
+    }
+}
+
+// Rows of cells that can be printed in every `Format`, so the formats
+// can't disagree about what a listing contains.
+pub struct Listing {
+    columns: Vec<&'static Column>,
+    // Position of each shown column in the full rows
+    shown: Vec<usize>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Listing {
+    // A listing showing `fields` (by key, in that order), or the default
+    // columns of `format` when `fields` is empty: the wide ones only for
+    // `Format::Wide` and the JSON and YAML documents, which have room for
+    // everything.
+    pub fn new(columns: &'static [Column], fields: &[String], format: Format) -> Result<Self> {
+        let everything = matches!(format, Format::Wide | Format::Json | Format::Yaml);
+        let shown = if fields.is_empty() {
+            (0..columns.len())
+                .filter(|&i| !columns[i].wide || everything)
+                .collect()
+        } else {
+            fields
+                .iter()
+                .map(|field| {
+                    columns.iter().position(|c| c.key == field).ok_or_else(|| {
+                        let valid: Vec<&str> = columns.iter().map(|c| c.key).collect();
+                        anyhow!(
+                            "Unknown field '{}'; valid fields: {}",
+                            field,
+                            valid.join(", ")
+                        )
+                    })
+                })
+                .collect::<Result<Vec<usize>>>()?
+        };
+        Ok(Listing {
+            columns: shown.iter().map(|&i| &columns[i]).collect(),
+            shown,
+            rows: Vec::new(),
+        })
+    }
+
+    // Add a row with a cell for every column, shown or not.
+    pub fn push(&mut self, row: Vec<Cell>) {
+        let mut cells: Vec<Option<Cell>> = row.into_iter().map(Some).collect();
+        let row = self
+            .shown
+            .iter()
+            .map(|&i| cells[i].take().unwrap_or_else(Cell::none))
+            .collect();
+        self.rows.push(row);
+    }
+
+    pub fn print(&self, format: Format) {
+        match format {
+            Format::Table | Format::Wide => {
+                let headers: Vec<&'static str> = self.columns.iter().map(|c| c.header).collect();
+                let mut table = Table::new(&headers);
+                for row in &self.rows {
+                    table.push(row.iter().map(|cell| cell.display.clone()).collect());
+                }
+                table.print_as(format);
+            }
+            Format::Json | Format::Yaml => print_data(format, &self.to_json()),
+            Format::Csv => print!("{}", self.to_csv()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        Value::Array(
+            self.rows
+                .iter()
+                .map(|row| {
+                    let mut value = Value::object();
+                    for (column, cell) in self.columns.iter().zip(row) {
+                        if cell.raw != Value::Null {
+                            value.set(column.key, cell.raw.clone());
+                        }
+                    }
+                    value
+                })
+                .collect(),
+        )
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::new();
+        let headers: Vec<String> = self.columns.iter().map(|c| csv_field(c.header)).collect();
+        out.push_str(&headers.join(","));
+        out.push_str("\r\n");
+        for row in &self.rows {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|cell| match &cell.raw {
+                    Value::Null => String::new(),
+                    Value::String(s) => csv_field(s),
+                    raw => csv_field(&raw.to_string()),
+                })
+                .collect();
+            out.push_str(&fields.join(","));
+            out.push_str("\r\n");
+        }
+        out
+    }
+}
+
+// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Epoch seconds as an ISO 8601 UTC timestamp.
+pub fn iso8601(secs: i64) -> String {
+    aws_sdk_ecs::primitives::DateTime::from_secs(secs)
+        .fmt(aws_sdk_ecs::primitives::DateTimeFormat::DateTime)
+        .unwrap_or_else(|_| secs.to_string())
+}
+
+// Rows of string cells under a header line, printed with aligned columns.
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&'static str]) -> Self {
+        Table {
+            headers: headers.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    // Print the table, narrowing the widest columns (with an ellipsis) when
+    // it would overflow the terminal.
+    pub fn print(&self) {
+        let term = console::Term::stdout();
+        let cols = term.is_term().then(|| term.size().1 as usize);
+        for line in self.render(cols) {
+            println!("{}", line);
+        }
+    }
+
+    // Print the table, truncated to the terminal or wide for `Format::Wide`.
+    pub fn print_as(&self, format: Format) {
+        match format {
+            Format::Wide => self.print_wide(),
+            _ => self.print(),
+        }
+    }
+
+    // Print the table without truncating anything. When it is wider than
+    // the terminal, each row is printed as a block of `HEADER  value` lines
+    // instead, so the terminal doesn't wrap the lines in the middle of an
+    // ARN.
+    pub fn print_wide(&self) {
+        let term = console::Term::stdout();
+        let cols = term.is_term().then(|| term.size().1 as usize);
+        let lines = self.render(None);
+        let overflows = cols.is_some_and(|cols| {
+            lines
+                .iter()
+                .any(|line| console::measure_text_width(line) > cols)
+        });
+        let lines = if overflows { self.records() } else { lines };
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+
+    // One block per row with a line per column, blocks separated by an
+    // empty line
+    fn records(&self) -> Vec<String> {
+        let width = self.headers.iter().map(|h| h.len()).max().unwrap_or(0);
+        let mut lines = Vec::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            for (header, cell) in self.headers.iter().zip(row) {
+                lines.push(format!("{:<width$}  {}", header, cell, width = width));
+            }
+        }
+        lines
+    }
+
+    // The header and row lines, narrowed to fit `cols` when given.
+    pub fn render(&self, cols: Option<usize>) -> Vec<String> {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(console::measure_text_width(cell));
+            }
+        }
+        if let Some(cols) = cols {
+            fit(&mut widths, cols);
+        }
+        let line = |cells: Vec<&str>| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| {
+                    // pad_str also truncates cells that fit exactly, so only
+                    // shorten the ones that really overflow
+                    let cell = if console::measure_text_width(cell) > *width {
+                        console::truncate_str(cell, *width, "…")
+                    } else {
+                        (*cell).into()
+                    };
+                    console::pad_str(&cell, *width, console::Alignment::Left, None).into_owned()
+                })
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+        let mut lines = vec![line(self.headers.clone())];
+        for row in &self.rows {
+            lines.push(line(row.iter().map(String::as_str).collect()));
+        }
+        lines
+    }
+}
+
+// Columns are never narrowed below this
+const MIN_COLUMN_WIDTH: usize = 8;
+
+// Shrink the widest column one step at a time until the table fits
+fn fit(widths: &mut [usize], cols: usize) {
+    let gaps = 2 * widths.len().saturating_sub(1);
+    while widths.iter().sum::<usize>() + gaps > cols {
+        let Some(widest) = widths.iter_mut().max() else {
+            return;
+        };
+        if *widest <= MIN_COLUMN_WIDTH {
+            return;
+        }
+        *widest -= 1;
+    }
+}
+
+// Print `value` as pretty JSON, or as YAML for `Format::Yaml`.
+pub fn print_data(format: Format, value: &Value) {
+    match format {
+        Format::Yaml => print!("{}", to_yaml(value)),
+        _ => println!("{}", value.to_pretty()),
+    }
+}
+
+// Block-style YAML for `value`. Keys keep their order and object fields
+// that are null are omitted.
+pub fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    match value {
+        Value::Array(items) if !items.is_empty() => write_yaml_items(&mut out, items, 0),
+        Value::Object(entries) if entries.iter().any(|(_, v)| *v != Value::Null) => {
+            write_yaml_entries(&mut out, entries, 0)
+        }
+        scalar => {
+            out.push_str(&yaml_scalar(scalar));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn write_yaml_entries(out: &mut String, entries: &[(String, Value)], indent: usize) {
+    let pad = " ".repeat(indent);
+    for (key, value) in entries {
+        let key = yaml_string(key);
+        match value {
+            Value::Null => {}
+            Value::Array(items) if !items.is_empty() => {
+                out.push_str(&format!("{}{}:\n", pad, key));
+                write_yaml_items(out, items, indent + 2);
+            }
+            Value::Object(fields) if fields.iter().any(|(_, v)| *v != Value::Null) => {
+                out.push_str(&format!("{}{}:\n", pad, key));
+                write_yaml_entries(out, fields, indent + 2);
+            }
+            scalar => out.push_str(&format!("{}{}: {}\n", pad, key, yaml_scalar(scalar))),
+        }
+    }
+}
+
+fn write_yaml_items(out: &mut String, items: &[Value], indent: usize) {
+    let pad = " ".repeat(indent);
+    for item in items {
+        let nested = match item {
+            Value::Array(items) if !items.is_empty() => {
+                let mut nested = String::new();
+                write_yaml_items(&mut nested, items, indent + 2);
+                nested
+            }
+            Value::Object(fields) if fields.iter().any(|(_, v)| *v != Value::Null) => {
+                let mut nested = String::new();
+                write_yaml_entries(&mut nested, fields, indent + 2);
+                nested
+            }
+            scalar => {
+                out.push_str(&format!("{}- {}\n", pad, yaml_scalar(scalar)));
+                continue;
+            }
+        };
+        // The first line of the nested block goes right after the dash
+        out.push_str(&pad);
+        out.push_str("- ");
+        out.push_str(&nested[indent + 2..]);
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_string(s),
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        other => other.to_string(),
+    }
+}
+
+// Plain when that can't be read as anything but this string, otherwise
+// double-quoted (JSON string syntax is valid YAML)
+fn yaml_string(s: &str) -> String {
+    let ambiguous = matches!(
+        s.to_lowercase().as_str(),
+        "" | "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n"
+    ) || yaml_number_or_date(s);
+    let plain = !ambiguous
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || "_-./@+:()".contains(c))
+        && !s.starts_with(['-', ':', '@'])
+        && !s.ends_with(':')
+        && !s.contains(": ");
+    if plain {
+        s.to_string()
+    } else {
+        Value::from(s).to_string()
+    }
+}
+
+// Whether a YAML 1.1 or 1.2 parser reads `s` as a number or a timestamp:
+// decimals, `.inf` and `.nan`, hex, octal and binary, `_` separators,
+// sexagesimal `1:30` and dates
+fn yaml_number_or_date(s: &str) -> bool {
+    let lower = s.to_lowercase();
+    let body = lower.strip_prefix(['+', '-']).unwrap_or(&lower);
+    let digits = |text: &str, radix: u32| {
+        !text.is_empty() && text.chars().all(|c| c == '_' || c.is_digit(radix))
+    };
+    let sexagesimal = body.split_once(':').is_some_and(|(first, rest)| {
+        digits(first, 10)
+            && rest.split(':').all(|part| {
+                !part.is_empty() && part.chars().all(|c| c.is_ascii_digit() || c == '.')
+            })
+    });
+    let date = body
+        .as_bytes()
+        .get(..5)
+        .is_some_and(|year| year[..4].iter().all(u8::is_ascii_digit) && year[4] == b'-');
+    s.parse::<f64>().is_ok()
+        || matches!(body, ".inf" | ".nan")
+        || body.strip_prefix("0x").is_some_and(|hex| digits(hex, 16))
+        || body
+            .strip_prefix("0o")
+            .is_some_and(|octal| digits(octal, 8))
+        || body
+            .strip_prefix("0b")
+            .is_some_and(|binary| digits(binary, 2))
+        || (body.starts_with(|c: char| c.is_ascii_digit())
+            && body.replace('_', "").parse::<f64>().is_ok())
+        || sexagesimal
+        || date
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(json: &str) -> String {
+        to_yaml(&crate::json::parse(json).unwrap())
+    }
+
+    #[test]
+    fn yaml_omits_null_fields() {
+        assert_eq!(yaml(r#"{"a": 1, "b": null, "c": "x"}"#), "a: 1\nc: x\n");
+        assert_eq!(yaml(r#"{"o": {"x": null}, "p": 2}"#), "o: {}\np: 2\n");
+        assert_eq!(yaml(r#"{"x": null}"#), "{}\n");
+        // Null items of a list keep their place
+        assert_eq!(yaml("[1, null]"), "- 1\n- null\n");
+    }
+
+    #[test]
+    fn yaml_nests_objects_and_arrays() {
+        let json = r#"{"tasks": [
+            {"id": "a", "ports": [80, 443], "tags": {"team": "web"}},
+            {"id": "b", "gone": null}
+        ]}"#;
+        let expected = "\
+tasks:
+  - id: a
+    ports:
+      - 80
+      - 443
+    tags:
+      team: web
+  - id: b
+";
+        assert_eq!(yaml(json), expected);
+        assert_eq!(
+            yaml("[[1, 2], [3], [{\"k\": true}]]"),
+            "- - 1\n  - 2\n- - 3\n- - k: true\n"
+        );
+        assert_eq!(yaml(r#"[{"a": {"b": [1]}}]"#), "- a:\n    b:\n      - 1\n");
+    }
+
+    #[test]
+    fn yaml_writes_empty_collections_inline() {
+        assert_eq!(yaml("[]"), "[]\n");
+        assert_eq!(yaml("{}"), "{}\n");
+        assert_eq!(yaml(r#"{"a": [], "b": {}}"#), "a: []\nb: {}\n");
+        assert_eq!(yaml("[[], {}]"), "- []\n- {}\n");
+    }
+
+    #[test]
+    fn yaml_quotes_ambiguous_scalars() {
+        let quoted = [
+            "",
+            "~",
+            "null",
+            "Null",
+            "true",
+            "False",
+            "yes",
+            "No",
+            "on",
+            "OFF",
+            "y",
+            "n",
+            "42",
+            "-7",
+            "3.14",
+            "1e3",
+            ".inf",
+            "-.Inf",
+            "+.INF",
+            ".nan",
+            ".NaN",
+            "0x1F",
+            "0o17",
+            "0b101",
+            "017",
+            "1_000",
+            "1:20",
+            "190:20:30.15",
+            "2024-01-31",
+            "2024-01-31T10:00:00Z",
+            "inf",
+            "NaN",
+            "-dash",
+            ":colon",
+            "@at",
+            "trailing:",
+            "a: b",
+            "with space",
+            "#hash",
+            "quote\"",
+            "new\nline",
+        ];
+        for value in quoted {
+            assert_eq!(
+                yaml_string(value),
+                Value::from(value).to_string(),
+                "{:?}",
+                value
+            );
+        }
+        let plain = [
+            "web",
+            "worker-2",
+            "app.v1",
+            "a:b",
+            "user@example.com",
+            "FARGATE_SPOT",
+            "3f9c0a7b",
+            "v1.2.3",
+            "1.2.3",
+            "x0x1F",
+            "0xZZ",
+            "12:ab",
+            "arn:aws:ecs:eu-west-1:1:task/uat/abc",
+        ];
+        for value in plain {
+            assert_eq!(yaml_string(value), value, "{:?}", value);
+        }
+    }
+}
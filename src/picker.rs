@@ -0,0 +1,356 @@
+//! Paged, type-to-filter selection prompt shared by every picker.
+
+use crate::failure::Kind;
+use anyhow::{anyhow, Result};
+use console::{style, AnsiCodeIterator, Key, Style, Term};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+// 0 means "derive from the terminal height"
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+static FIRST: AtomicBool = AtomicBool::new(false);
+static CONTEXT: OnceLock<String> = OnceLock::new();
+
+const MIN_PAGE_SIZE: usize = 5;
+// Prompt and status lines drawn around the items
+const CHROME_LINES: usize = 3;
+
+// Override the page size for every picker in this run.
+pub fn set_page_size(size: Option<usize>) {
+    PAGE_SIZE.store(size.unwrap_or(0), Ordering::Relaxed);
+}
+
+// Disable prompting: a single candidate is taken as-is and anything more
+// is an error listing the candidates.
+pub fn set_interactive(interactive: bool) {
+    INTERACTIVE.store(interactive, Ordering::Relaxed);
+}
+
+// Whether a person can be asked to choose.
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+// Take the first item of every picker instead of prompting, saying what
+// was taken.
+pub fn set_first(first: bool) {
+    FIRST.store(first, Ordering::Relaxed);
+}
+
+// Show the active context in front of every prompt.
+pub fn set_context(name: &str) {
+    let _ = CONTEXT.set(name.to_string());
+}
+
+pub struct Picker<'a> {
+    prompt: &'a str,
+    items: &'a [String],
+    highlight: Option<&'a str>,
+    start: usize,
+}
+
+impl<'a> Picker<'a> {
+    pub fn new(prompt: &'a str, items: &'a [String]) -> Self {
+        Picker {
+            prompt,
+            items,
+            highlight: None,
+            start: 0,
+        }
+    }
+
+    // Start with the cursor on item `index` (e.g. the one chosen last
+    // time) instead of the first; ignored when out of range.
+    pub fn start_at(mut self, index: Option<usize>) -> Self {
+        self.start = index.filter(|&i| i < self.items.len()).unwrap_or(0);
+        self
+    }
+
+    // Highlight `text` in the items while nothing is typed, e.g. the
+    // partial name they were pre-filtered by.
+    pub fn highlight(mut self, text: Option<&'a str>) -> Self {
+        self.highlight = text.filter(|text| !text.is_empty());
+        self
+    }
+
+    // Run the prompt and return the index of the chosen item.
+    pub fn interact(self) -> Result<usize> {
+        Ok(self.prompt_for(false)?[0])
+    }
+
+    // Run the prompt with space toggling items, and return the indices of
+    // the chosen ones in list order. Enter with none toggled takes the one
+    // under the cursor.
+    pub fn interact_multi(self) -> Result<Vec<usize>> {
+        self.prompt_for(true)
+    }
+
+    fn prompt_for(&self, multi: bool) -> Result<Vec<usize>> {
+        if self.items.is_empty() {
+            return Err(anyhow!("Nothing to select for '{}'", self.prompt));
+        }
+        if FIRST.load(Ordering::Relaxed) {
+            eprintln!(
+                "{} {} {} {}",
+                style("✔").green(),
+                self.title(),
+                style(&self.items[0]).bold(),
+                style(format!("(first of {})", self.items.len())).dim()
+            );
+            return Ok(vec![0]);
+        }
+        if !INTERACTIVE.load(Ordering::Relaxed) {
+            if self.items.len() == 1 {
+                return Ok(vec![0]);
+            }
+            return Err(anyhow!(
+                "'{}' needs a choice but prompting is disabled; candidates:\n  {}",
+                self.prompt,
+                self.items.join("\n  ")
+            ));
+        }
+        let term = Term::stderr();
+        if !term.is_term() {
+            return Err(anyhow!("'{}' needs an interactive terminal", self.prompt));
+        }
+
+        term.hide_cursor()?;
+        let result = self.run(&term, multi.then(|| vec![false; self.items.len()]));
+        term.show_cursor()?;
+        result
+    }
+
+    // The prompt, prefixed with the active context if there is one
+    fn title(&self) -> String {
+        match CONTEXT.get() {
+            Some(context) => format!(
+                "{} {}",
+                style(format!("[{}]", context)).magenta(),
+                self.prompt
+            ),
+            None => self.prompt.to_string(),
+        }
+    }
+
+    fn page_size(&self, term: &Term) -> usize {
+        match PAGE_SIZE.load(Ordering::Relaxed) {
+            0 => {
+                let (rows, _) = term.size();
+                (rows as usize)
+                    .saturating_sub(CHROME_LINES)
+                    .max(MIN_PAGE_SIZE)
+            }
+            size => size,
+        }
+    }
+
+    // `toggled` holds the toggle of every item in multi-select mode
+    fn run(&self, term: &Term, mut toggled: Option<Vec<bool>>) -> Result<Vec<usize>> {
+        let mut filter = String::new();
+        let mut matches: Vec<usize> = (0..self.items.len()).collect();
+        let mut cursor = self.start;
+        let mut drawn = 0;
+
+        loop {
+            let page_size = self.page_size(term);
+            term.clear_last_lines(drawn)?;
+            drawn = self.draw(
+                term,
+                &filter,
+                &matches,
+                cursor,
+                toggled.as_deref(),
+                page_size,
+            )?;
+
+            match term.read_key()? {
+                Key::Enter if !matches.is_empty() => {
+                    let mut chosen: Vec<usize> = toggled
+                        .iter()
+                        .flat_map(|toggled| toggled.iter().enumerate())
+                        .filter(|(_, on)| **on)
+                        .map(|(i, _)| i)
+                        .collect();
+                    if chosen.is_empty() {
+                        chosen.push(matches[cursor]);
+                    }
+                    let shown: Vec<&str> = chosen.iter().map(|&i| self.items[i].as_str()).collect();
+                    term.clear_last_lines(drawn)?;
+                    term.write_line(&format!(
+                        "{} {} {}",
+                        style("✔").green(),
+                        self.title(),
+                        style(shown.join(", ")).bold()
+                    ))?;
+                    return Ok(chosen);
+                }
+                Key::Char(' ') if toggled.is_some() && !matches.is_empty() => {
+                    if let Some(toggled) = toggled.as_mut() {
+                        toggled[matches[cursor]] ^= true;
+                    }
+                    cursor = (cursor + 1).min(matches.len() - 1);
+                }
+                Key::ArrowUp if !matches.is_empty() => {
+                    cursor = (cursor + matches.len() - 1) % matches.len();
+                }
+                Key::ArrowDown | Key::Tab if !matches.is_empty() => {
+                    cursor = (cursor + 1) % matches.len();
+                }
+                Key::PageUp | Key::ArrowLeft => cursor = cursor.saturating_sub(page_size),
+                Key::PageDown | Key::ArrowRight if !matches.is_empty() => {
+                    cursor = (cursor + page_size).min(matches.len() - 1);
+                }
+                Key::Home => cursor = 0,
+                Key::End => cursor = matches.len().saturating_sub(1),
+                Key::Backspace => {
+                    filter.pop();
+                    matches = self.filter(&filter);
+                    cursor = 0;
+                }
+                Key::Escape if !filter.is_empty() => {
+                    filter.clear();
+                    matches = self.filter(&filter);
+                    cursor = 0;
+                }
+                Key::Escape | Key::CtrlC => {
+                    term.clear_last_lines(drawn)?;
+                    return Err(Kind::Aborted.error("Selection cancelled"));
+                }
+                Key::Char(c) if !c.is_control() => {
+                    filter.push(c);
+                    matches = self.filter(&filter);
+                    cursor = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn filter(&self, filter: &str) -> Vec<usize> {
+        let needle = filter.to_lowercase();
+        self.items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                console::strip_ansi_codes(item)
+                    .to_lowercase()
+                    .contains(&needle)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Draw the prompt, the current page and the status line; returns the
+    // number of lines written so the next frame can clear them
+    fn draw(
+        &self,
+        term: &Term,
+        filter: &str,
+        matches: &[usize],
+        cursor: usize,
+        toggled: Option<&[bool]>,
+        page_size: usize,
+    ) -> Result<usize> {
+        let (_, cols) = term.size();
+        let width = (cols as usize).saturating_sub(2).max(10);
+        let mut lines = 0;
+        let header = if filter.is_empty() {
+            format!("{} {}", style("?").yellow(), self.title())
+        } else {
+            format!(
+                "{} {} {}",
+                style("?").yellow(),
+                self.title(),
+                style(filter).cyan()
+            )
+        };
+        term.write_line(&header)?;
+        lines += 1;
+
+        if matches.is_empty() {
+            term.write_line(&format!("  {}", style("(no matches)").dim()))?;
+            lines += 1;
+        }
+
+        let needle = match filter {
+            "" => self.highlight.unwrap_or_default(),
+            filter => filter,
+        };
+        let page = cursor / page_size;
+        let pages = matches.len().div_ceil(page_size).max(1);
+        let start = page * page_size;
+        for (offset, &index) in matches.iter().skip(start).take(page_size).enumerate() {
+            let selected = start + offset == cursor;
+            let base = if selected {
+                Style::new().cyan()
+            } else {
+                Style::new()
+            };
+            let mut item = highlight(&self.items[index], needle, &base);
+            if let Some(toggled) = toggled {
+                let mark = if toggled[index] { "[x]" } else { "[ ]" };
+                item = format!("{} {}", base.apply_to(mark), item);
+            }
+            let item = console::truncate_str(&item, width, "…");
+            if selected {
+                term.write_line(&format!("{} {}", style(">").cyan(), item))?;
+            } else {
+                term.write_line(&format!("  {}", item))?;
+            }
+            lines += 1;
+        }
+
+        let mut status = format!(
+            "page {}/{} · {} of {} · type to filter",
+            page + 1,
+            pages,
+            matches.len(),
+            self.items.len()
+        );
+        if let Some(toggled) = toggled {
+            let count = toggled.iter().filter(|on| **on).count();
+            status.push_str(&format!(" · space to select · {} selected", count));
+        }
+        term.write_line(&format!("{}", style(status).dim()))?;
+        lines += 1;
+        Ok(lines)
+    }
+}
+
+// Style `item` with `base`, and its first case-insensitive occurrence of
+// `needle` bold yellow. Only the item's visible text is matched, so escape
+// codes already in it are kept and padded columns stay aligned.
+fn highlight(item: &str, needle: &str, base: &Style) -> String {
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let needle: Vec<char> = needle.chars().map(fold).collect();
+    // Visible characters with their byte range in `item`
+    let mut visible = Vec::new();
+    let mut offset = 0;
+    for (segment, is_ansi) in AnsiCodeIterator::new(item) {
+        if !is_ansi {
+            for (i, c) in segment.char_indices() {
+                visible.push((offset + i, offset + i + c.len_utf8(), fold(c)));
+            }
+        }
+        offset += segment.len();
+    }
+    let found = (!needle.is_empty())
+        .then(|| {
+            visible
+                .windows(needle.len())
+                .position(|window| window.iter().map(|(_, _, c)| *c).eq(needle.iter().copied()))
+        })
+        .flatten();
+    let Some(first) = found else {
+        return base.apply_to(item).to_string();
+    };
+    let from = visible[first].0;
+    let to = visible[first + needle.len() - 1].1;
+    format!(
+        "{}{}{}",
+        base.apply_to(&item[..from]),
+        base.clone().yellow().bold().apply_to(&item[from..to]),
+        base.apply_to(&item[to..])
+    )
+}
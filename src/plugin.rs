@@ -0,0 +1,88 @@
+//! Locating session-manager-plugin.
+
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+pub const ENV_VAR: &str = "ECS_REMOTE_PLUGIN_PATH";
+
+const BINARY: &str = if cfg!(windows) {
+    "session-manager-plugin.exe"
+} else {
+    "session-manager-plugin"
+};
+
+static PLUGIN: OnceLock<PathBuf> = OnceLock::new();
+
+// Use the plugin at `path` (the binary or the directory holding it) for
+// this run, after checking that it can be executed.
+pub fn set_path(path: &Path) -> Result<()> {
+    let binary = if path.is_dir() {
+        path.join(BINARY)
+    } else {
+        path.to_path_buf()
+    };
+    if !binary.is_file() {
+        return Err(anyhow!(
+            "session-manager-plugin not found at {}",
+            binary.display()
+        ));
+    }
+    if !is_executable(&binary) {
+        return Err(anyhow!("{} is not executable", binary.display()));
+    }
+    if binary.file_name().and_then(|n| n.to_str()) != Some(BINARY) {
+        return Err(anyhow!(
+            "{} must be named {} for the AWS CLI to find it",
+            binary.display(),
+            BINARY
+        ));
+    }
+    let _ = PLUGIN.set(binary);
+    Ok(())
+}
+
+// The plugin that sessions will use: the configured one, or the first on
+// `PATH`.
+pub fn resolve() -> Option<PathBuf> {
+    if let Some(binary) = PLUGIN.get() {
+        return Some(binary.clone());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(BINARY))
+        .find(|binary| binary.is_file() && is_executable(binary))
+}
+
+// The plugin's `--version` output.
+pub fn version(binary: &Path) -> Option<String> {
+    let output = Command::new(binary).arg("--version").output().ok()?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !version.is_empty()).then_some(version)
+}
+
+// Let an AWS CLI process find the configured plugin.
+pub fn apply(cli: &mut Command) {
+    let Some(dir) = PLUGIN.get().and_then(|binary| binary.parent()) else {
+        return;
+    };
+    let mut dirs = vec![dir.to_path_buf()];
+    if let Some(path) = std::env::var_os("PATH") {
+        dirs.extend(std::env::split_paths(&path));
+    }
+    if let Ok(path) = std::env::join_paths(dirs) {
+        cli.env("PATH", path);
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
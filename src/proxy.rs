@@ -0,0 +1,258 @@
+//! HTTP(S) proxy for the SDK calls.
+
+use crate::failure::Kind;
+use crate::tls;
+use anyhow::Result;
+use aws_smithy_runtime::client::http::hyper_014::HyperClientBuilder;
+use aws_smithy_runtime_api::client::http::SharedHttpClient;
+use base64::Engine;
+use hyper::service::Service;
+use hyper::Uri;
+use hyper_rustls::HttpsConnector;
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Command;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+static PROXY: OnceLock<Option<Proxy>> = OnceLock::new();
+
+// Largest CONNECT response we wait for
+const MAX_RESPONSE_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+struct Proxy {
+    // As given, for the AWS CLI with --proxy-url
+    url: String,
+    host: String,
+    port: u16,
+    // `Basic …`, when the URL has credentials
+    authorization: Option<String>,
+    no_proxy: Vec<String>,
+    // Where the URL came from: --proxy-url or the variable
+    source: String,
+}
+
+// Pick the proxy from `--proxy-url` or the environment. Returns what
+// `--verbose` reports: the proxy (without its credentials) and where it
+// came from, or None without one.
+pub fn configure(flag: Option<&str>) -> Result<Option<String>> {
+    let from_env = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]
+        .iter()
+        .find_map(|var| {
+            let value = std::env::var(var).ok().filter(|value| !value.is_empty())?;
+            Some((value, var.to_string()))
+        });
+    let chosen = match flag {
+        Some(url) => Some((url.to_string(), "--proxy-url".to_string())),
+        None => from_env,
+    };
+    let proxy = match chosen {
+        Some((url, source)) => Some(parse(&url, source)?),
+        None => None,
+    };
+    let description = proxy
+        .as_ref()
+        .map(|proxy| format!("http://{}:{} ({})", proxy.host, proxy.port, proxy.source));
+    let _ = PROXY.set(proxy);
+    Ok(description)
+}
+
+fn parse(url: &str, source: String) -> Result<Proxy> {
+    let invalid = |why: &str| {
+        Kind::Usage.error(format!(
+            "Invalid proxy URL '{}' from {}: {}",
+            redact(url),
+            source,
+            why
+        ))
+    };
+    let full = if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("http://{}", url)
+    };
+    let uri: Uri = full.parse().map_err(|_| invalid("not a URL"))?;
+    if uri.scheme_str() != Some("http") {
+        return Err(invalid("only http:// proxies are supported"));
+    }
+    let authority = uri.authority().ok_or_else(|| invalid("no host"))?;
+    let authorization = authority.as_str().rsplit_once('@').map(|(userinfo, _)| {
+        let (user, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+        let credentials = format!(
+            "{}:{}",
+            crate::percent_decode(user),
+            crate::percent_decode(password)
+        );
+        format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        )
+    });
+    let no_proxy = ["NO_PROXY", "no_proxy"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .unwrap_or_default()
+        .split(',')
+        .map(|entry| entry.trim().to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    Ok(Proxy {
+        url: url.to_string(),
+        host: authority.host().to_string(),
+        port: authority.port_u16().unwrap_or(80),
+        authorization,
+        no_proxy,
+        source,
+    })
+}
+
+// The URL without the password, for messages
+fn redact(url: &str) -> String {
+    match url.rsplit_once('@') {
+        Some((userinfo, host)) => {
+            let (scheme, user) = userinfo.rsplit_once("//").unwrap_or(("", userinfo));
+            let user = user.split(':').next().unwrap_or_default();
+            let scheme = if scheme.is_empty() {
+                String::new()
+            } else {
+                format!("{}//", scheme)
+            };
+            format!("{}{}:***@{}", scheme, user, host)
+        }
+        None => url.to_string(),
+    }
+}
+
+// An HTTP client for the SDK when the default one won't do: to go through
+// the proxy or to trust a custom CA bundle (see `tls`).
+pub fn http_client() -> Option<SharedHttpClient> {
+    if PROXY.get().cloned().flatten().is_none() && tls::client_config().is_none() {
+        return None;
+    }
+    Some(HyperClientBuilder::new().build(connector()))
+}
+
+// A connector for our own HTTP requests, through the proxy and trusting the
+// CA bundle when they are set
+pub fn connector() -> HttpsConnector<Tunnel> {
+    let proxy = PROXY.get().cloned().flatten();
+    let builder = hyper_rustls::HttpsConnectorBuilder::new();
+    let builder = match tls::client_config() {
+        Some(config) => builder.with_tls_config(config),
+        None => builder.with_native_roots(),
+    };
+    builder
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(Tunnel { proxy })
+}
+
+// Hand `--proxy-url` on to an AWS CLI process; a proxy from the
+// environment reaches it by itself.
+pub fn apply(cli: &mut Command) {
+    if let Some(Some(proxy)) = PROXY.get() {
+        if proxy.source == "--proxy-url" {
+            cli.env("HTTPS_PROXY", &proxy.url)
+                .env("HTTP_PROXY", &proxy.url);
+        }
+    }
+}
+
+// Whether to skip the proxy for `host`: always for loopback and link-local
+// addresses (instance and container metadata), otherwise when NO_PROXY has
+// `*`, the host itself or a domain it is in (with or without the leading
+// dot). Ports are ignored.
+fn bypass(no_proxy: &[String], host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let local = match host.trim_matches(['[', ']']).parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(ip)) => ip.is_loopback() || ip.is_link_local(),
+        Ok(std::net::IpAddr::V6(ip)) => ip.is_loopback() || ip.segments()[0] == 0xfd00,
+        Err(_) => host == "localhost",
+    };
+    local
+        || no_proxy.iter().any(|entry| {
+            let entry = entry
+                .rsplit_once(':')
+                .map_or(entry.as_str(), |(name, port)| match port.parse::<u16>() {
+                    Ok(_) => name,
+                    Err(_) => entry,
+                });
+            let domain = entry.trim_start_matches("*.").trim_start_matches('.');
+            entry == "*" || host == domain || host.ends_with(&format!(".{}", domain))
+        })
+}
+
+// Connects through a CONNECT tunnel to the proxy, or directly without one
+// and for NO_PROXY hosts
+#[derive(Clone)]
+pub struct Tunnel {
+    proxy: Option<Proxy>,
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl Service<Uri> for Tunnel {
+    type Response = TcpStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<TcpStream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            let host = uri.host().ok_or("request without a host")?.to_string();
+            let default_port = if uri.scheme_str() == Some("http") {
+                80
+            } else {
+                443
+            };
+            let port = uri.port_u16().unwrap_or(default_port);
+            let Some(proxy) = proxy.filter(|proxy| !bypass(&proxy.no_proxy, &host)) else {
+                return Ok(TcpStream::connect((host.as_str(), port)).await?);
+            };
+            let mut stream = TcpStream::connect((proxy.host.as_str(), proxy.port))
+                .await
+                .map_err(|err| {
+                    format!("cannot reach proxy {}:{}: {}", proxy.host, proxy.port, err)
+                })?;
+            let mut request = format!(
+                "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+                host = host,
+                port = port
+            );
+            if let Some(authorization) = &proxy.authorization {
+                request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+            }
+            request.push_str("\r\n");
+            stream.write_all(request.as_bytes()).await?;
+
+            // Read the response headers byte by byte, so nothing after them
+            // is taken from the tunnel
+            let mut response = Vec::new();
+            while !response.ends_with(b"\r\n\r\n") {
+                if response.len() > MAX_RESPONSE_BYTES {
+                    return Err("proxy sent an oversized CONNECT response".into());
+                }
+                let byte = stream.read_u8().await?;
+                response.push(byte);
+            }
+            let status_line = String::from_utf8_lossy(&response);
+            let status_line = status_line.lines().next().unwrap_or_default();
+            let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+            if status != "200" {
+                return Err(format!(
+                    "proxy {}:{} refused CONNECT to {}:{}: {}",
+                    proxy.host, proxy.port, host, port, status_line
+                )
+                .into());
+            }
+            Ok(stream)
+        })
+    }
+}
@@ -0,0 +1,81 @@
+//! Choosing a task without asking.
+
+use crate::failure::Kind;
+use crate::TaskInfo;
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+// The best task and why it ranked first.
+pub fn best(
+    tasks: &[TaskInfo],
+    container: Option<&str>,
+    rolled_back: &HashSet<String>,
+) -> Result<(TaskInfo, String)> {
+    let candidates: Vec<&TaskInfo> = tasks
+        .iter()
+        .filter(|task| agent_running(task, container) && !task.platform_too_old)
+        .collect();
+    let best = candidates
+        .iter()
+        .max_by_key(|task| {
+            (
+                !is_rolled_back(task, rolled_back),
+                health_score(&task.health),
+                task.revision,
+                // Earlier start means longer uptime
+                task.started_at.map(Reverse),
+            )
+        })
+        .ok_or_else(|| {
+            Kind::NoExecReadyTasks.error(format!(
+                "None of the {} tasks has a running exec agent",
+                tasks.len()
+            ))
+        })?;
+
+    let mut reasons = vec![best.health.clone(), "exec agent RUNNING".to_string()];
+    if let Some(revision) = best.revision {
+        reasons.push(format!("revision {}", revision));
+    }
+    if let Some(started_at) = best.started_at {
+        reasons.push(format!(
+            "up {}",
+            crate::format_age(crate::state::now_secs() - started_at).trim_end_matches(" ago")
+        ));
+    }
+    if is_rolled_back(best, rolled_back) {
+        reasons.push("from a deployment being rolled back".to_string());
+    }
+    let reason = format!(
+        "{} (best of {} candidates)",
+        reasons.join(", "),
+        candidates.len()
+    );
+    Ok(((*best).clone(), reason))
+}
+
+// Healthy beats unknown beats unhealthy.
+pub fn health_score(health: &str) -> u8 {
+    match health {
+        "HEALTHY" => 2,
+        "UNHEALTHY" => 0,
+        _ => 1,
+    }
+}
+
+// The agent of the target container (by name or index), or of any
+// container when it isn't known yet
+fn agent_running(task: &TaskInfo, container: Option<&str>) -> bool {
+    let target = container.map(|spec| crate::lookup_container(&task.containers, spec));
+    task.exec_agents
+        .iter()
+        .filter(|(name, _)| target.is_none_or(|target| target == Some(name)))
+        .any(|(_, status)| status == "RUNNING")
+}
+
+fn is_rolled_back(task: &TaskInfo, rolled_back: &HashSet<String>) -> bool {
+    task.started_by
+        .as_ref()
+        .is_some_and(|deployment| rolled_back.contains(deployment))
+}
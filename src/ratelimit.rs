@@ -0,0 +1,219 @@
+//! A limit on the rate of AWS API calls (`--api-rps`).
+
+use crate::failure::Kind;
+use anyhow::Result;
+use aws_sdk_ecs::config::interceptors::AfterDeserializationInterceptorContextRef;
+use aws_sdk_ecs::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_smithy_runtime_api::box_error::BoxError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static BUCKET: OnceLock<Mutex<Bucket>> = OnceLock::new();
+
+static THROTTLED: AtomicUsize = AtomicUsize::new(0);
+
+// The slowest rate, a call every 100s; waits stay well within a Duration
+const MIN_RPS: f64 = 0.01;
+
+// Error codes AWS answers with when a caller goes over the rate limit
+const THROTTLING_CODES: &[&str] = &[
+    "ThrottlingException",
+    "Throttling",
+    "TooManyRequestsException",
+    "RequestLimitExceeded",
+];
+
+struct Bucket {
+    // Tokens added per second, and the most the bucket holds
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    // A full bucket for `rps` calls a second. Below one a second it still
+    // holds one token, so a call can go at all.
+    fn new(rps: f64, now: Instant) -> Result<Bucket> {
+        if !rps.is_finite() || rps < MIN_RPS {
+            return Err(Kind::Usage.error(format!(
+                "The API rate must be at least {} requests a second, not {}",
+                MIN_RPS, rps
+            )));
+        }
+        let capacity = rps.max(1.0);
+        Ok(Bucket {
+            rate: rps,
+            capacity,
+            tokens: capacity,
+            refilled: now,
+        })
+    }
+
+    // Take a token at `now`, or say how long until there is one
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.refilled = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return None;
+        }
+        Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+}
+
+// Limit the calls to `rps` a second, from `--api-rps` or the config file.
+// Returns what `--verbose` reports.
+pub fn configure(rps: Option<f64>) -> Result<String> {
+    let Some(rps) = rps else {
+        return Ok("unlimited".to_string());
+    };
+    let _ = BUCKET.set(Mutex::new(Bucket::new(rps, Instant::now())?));
+    Ok(format!("{} requests/s", rps))
+}
+
+// Wait for a token, when there is a limit.
+pub async fn acquire() {
+    let Some(bucket) = BUCKET.get() else {
+        return;
+    };
+    loop {
+        let wait = bucket
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take(Instant::now());
+        match wait {
+            Some(wait) => tokio::time::sleep(wait).await,
+            None => return,
+        }
+    }
+}
+
+// How many responses so far said the calls were throttled.
+pub fn throttled() -> usize {
+    THROTTLED.load(Ordering::Relaxed)
+}
+
+// Counts the throttled responses of an SDK client, retried or not.
+#[derive(Debug)]
+pub struct CountThrottling;
+
+impl Intercept for CountThrottling {
+    fn name(&self) -> &'static str {
+        "CountThrottling"
+    }
+
+    fn read_after_deserialization(
+        &self,
+        context: &AfterDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let response = context.response();
+        let status = response.status().as_u16();
+        // The error code is in the (already read) body of JSON protocol
+        // errors, and in a header for some services
+        let body = response
+            .body()
+            .bytes()
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        let header = response
+            .headers()
+            .get("x-amzn-errortype")
+            .unwrap_or_default();
+        let throttled = status == 429
+            || (status >= 400
+                && THROTTLING_CODES
+                    .iter()
+                    .any(|code| body.contains(code) || header.starts_with(code)));
+        if throttled {
+            THROTTLED.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Take tokens at `now` until one is refused, and say how many were given
+    fn drain(bucket: &mut Bucket, now: Instant) -> (usize, Duration) {
+        let mut taken = 0;
+        loop {
+            match bucket.take(now) {
+                None => taken += 1,
+                Some(wait) => return (taken, wait),
+            }
+        }
+    }
+
+    fn assert_close(actual: Duration, expected: Duration) {
+        let diff = actual.as_secs_f64() - expected.as_secs_f64();
+        assert!(diff.abs() < 1e-6, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn full_bucket_allows_a_burst() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(5.0, start).unwrap();
+        let (taken, wait) = drain(&mut bucket, start);
+        assert_eq!(taken, 5);
+        assert_close(wait, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn refills_at_the_rate() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(4.0, start).unwrap();
+        drain(&mut bucket, start);
+        // A token every 250ms
+        assert!(bucket.take(start + Duration::from_millis(100)).is_some());
+        assert_eq!(bucket.take(start + Duration::from_millis(250)), None);
+        let (taken, _) = drain(&mut bucket, start + Duration::from_millis(750));
+        assert_eq!(taken, 2);
+        // Never more than the capacity, however long it was idle
+        let (taken, _) = drain(&mut bucket, start + Duration::from_secs(60));
+        assert_eq!(taken, 4);
+    }
+
+    #[test]
+    fn slow_rates_still_hold_one_token() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(0.5, start).unwrap();
+        let (taken, wait) = drain(&mut bucket, start);
+        assert_eq!(taken, 1);
+        assert_close(wait, Duration::from_secs(2));
+        assert!(bucket.take(start + Duration::from_secs(1)).is_some());
+        assert_eq!(bucket.take(start + Duration::from_secs(2)), None);
+        // A quarter token half a second later, so 1.5s to go
+        let wait = bucket.take(start + Duration::from_millis(2500)).unwrap();
+        assert_close(wait, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn rejects_rates_that_are_not_positive() {
+        let rates = [
+            0.0,
+            -0.0,
+            -1.0,
+            0.009,
+            1e-20,
+            f64::MIN_POSITIVE,
+            f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        for rps in rates {
+            let err = Bucket::new(rps, Instant::now()).err().unwrap();
+            assert_eq!(crate::failure::classify(&err), Kind::Usage, "{}", rps);
+        }
+        let start = Instant::now();
+        let mut slowest = Bucket::new(MIN_RPS, start).unwrap();
+        drain(&mut slowest, start);
+        assert_close(slowest.take(start).unwrap(), Duration::from_secs(100));
+    }
+}
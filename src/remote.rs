@@ -0,0 +1,562 @@
+//! Building the command string that runs inside the container.
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use std::path::Path;
+
+// Shell started when none is given; `sh` takes over where it is missing.
+pub const DEFAULT_SHELL: &str = "/bin/bash";
+
+// Shell started on Windows tasks.
+pub const POWERSHELL: &str = "powershell.exe";
+
+// Largest script accepted through `--stdin`/`--script`; the base64 form has
+// to fit in a single execute-command request.
+pub const MAX_SCRIPT_BYTES: usize = 32 * 1024;
+
+// Scripts end by printing this followed by their exit status, since the
+// AWS CLI doesn't propagate the remote exit code.
+pub const EXIT_MARKER: &str = "__ECS_REMOTE_EXIT__=";
+
+// Quote a value as a single POSIX sh word.
+pub fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c))
+    {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+// Quote a value as a single PowerShell word (a verbatim string).
+// PowerShell takes the typographic single quotes for quotes as well, so
+// they are doubled like `'`.
+pub fn quote_powershell(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:\\".contains(c))
+    {
+        return value.to_string();
+    }
+    let mut quoted = String::from("'");
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+// PowerShell that runs a program with arguments, each taken literally.
+pub fn powershell_argv(words: &[String]) -> String {
+    let words: Vec<String> = words.iter().map(|word| quote_powershell(word)).collect();
+    format!("& {}", words.join(" "))
+}
+
+// Command for a Windows task: PowerShell `code` run from `dir` with `vars`
+// set, or an interactive PowerShell there when there is no code. It
+// travels as `-EncodedCommand` (base64 of UTF-16LE), so nothing in it
+// needs escaping on the way.
+pub fn powershell(dir: Option<&str>, vars: &[(String, String)], code: Option<&str>) -> String {
+    let mut statements = Vec::new();
+    // A missing directory is reported and the session starts anyway
+    if let Some(dir) = dir {
+        statements.push(format!(
+            "Set-Location -LiteralPath {}",
+            quote_powershell(dir)
+        ));
+    }
+    for (key, value) in vars {
+        statements.push(format!("$env:{} = {}", key, quote_powershell(value)));
+    }
+    let flag = match code {
+        Some(code) => {
+            statements.push(code.to_string());
+            "-NoProfile"
+        }
+        None if statements.is_empty() => return POWERSHELL.to_string(),
+        None => "-NoExit",
+    };
+    let utf16: Vec<u8> = statements
+        .join("; ")
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    format!(
+        "{} {} -EncodedCommand {}",
+        POWERSHELL,
+        flag,
+        base64::engine::general_purpose::STANDARD.encode(utf16)
+    )
+}
+
+// Command that starts the interactive shell, as a login shell (`-l`) when
+// `login` is set. Without an explicit `shell` it probes for bash and falls
+// back to `sh`, so images without bash (Alpine, distroless-ish) still work.
+pub fn shell_command(shell: Option<&str>, login: bool) -> String {
+    let flag = if login { " -l" } else { "" };
+    match shell {
+        Some(shell) => format!("{}{}", shell, flag),
+        None => format!(
+            "sh -c {}",
+            quote(&format!(
+                "if [ -x {bash} ]; then exec {bash}{flag}; else exec sh{flag}; fi",
+                bash = DEFAULT_SHELL,
+                flag = flag
+            ))
+        ),
+    }
+}
+
+// Command that decodes `script` remotely and feeds it to `sh -s`. The
+// script travels base64-encoded so its quotes and newlines can't interfere
+// with the command parsing on the way.
+pub fn stdin_script(script: &[u8]) -> Result<String> {
+    let encoded = encode_script(script, "stdin")?;
+    Ok(format!(
+        "sh -c {}",
+        quote(&format!(
+            "echo {} | base64 -d | sh -s; echo {}$?",
+            encoded, EXIT_MARKER
+        ))
+    ))
+}
+
+// Command that writes `script` to a temp file, runs it with `args` and
+// removes it again whatever the outcome. The file is executed directly so
+// its shebang is honored, falling back to `sh` when it can't be made
+// executable (or the filesystem refuses to execute it).
+pub fn file_script(script: &[u8], args: &[String]) -> Result<String> {
+    let encoded = encode_script(script, "--script")?;
+    let args: Vec<String> = args.iter().map(|arg| quote(arg)).collect();
+    let args = args.join(" ");
+    let body = format!(
+        concat!(
+            "f=$(mktemp 2>/dev/null || echo /tmp/ecs_remote.$$); ",
+            "trap 'rm -f \"$f\"' EXIT; ",
+            "echo {encoded} | base64 -d > \"$f\" || exit 1; ",
+            "if chmod +x \"$f\" 2>/dev/null; then \"$f\" {args}; rc=$?; ",
+            "[ $rc -eq 126 ] && {{ sh \"$f\" {args}; rc=$?; }}; ",
+            "else sh \"$f\" {args}; rc=$?; fi; ",
+            "echo {marker}$rc"
+        ),
+        encoded = encoded,
+        args = args,
+        marker = EXIT_MARKER
+    );
+    Ok(format!("sh -c {}", quote(&body)))
+}
+
+fn encode_script(script: &[u8], source: &str) -> Result<String> {
+    if script.is_empty() {
+        return Err(anyhow!("No script received on {}", source));
+    }
+    if script.len() > MAX_SCRIPT_BYTES {
+        return Err(anyhow!(
+            "Script from {} is {} bytes; the limit is {} bytes",
+            source,
+            script.len(),
+            MAX_SCRIPT_BYTES
+        ));
+    }
+    Ok(base64::engine::general_purpose::STANDARD.encode(script))
+}
+
+// Parse a `KEY=VALUE` pair; the value is taken verbatim.
+pub fn parse_env(spec: &str) -> Result<(String, String)> {
+    let (key, value) = spec.split_once('=').ok_or_else(|| {
+        anyhow!(
+            "Invalid environment variable '{}': expected KEY=VALUE",
+            spec
+        )
+    })?;
+    let valid = key
+        .chars()
+        .enumerate()
+        .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+    if key.is_empty() || !valid {
+        return Err(anyhow!("Invalid environment variable name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+// Read `KEY=VALUE` lines from a local file. Blank lines and `#` comments are
+// skipped and an `export ` prefix is allowed; values are not unquoted.
+pub fn read_env_file(path: &Path) -> Result<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read env file {}", path.display()))?;
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(number, line)| {
+            let line = line.trim_start();
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            parse_env(line).with_context(|| format!("{}:{}", path.display(), number + 1))
+        })
+        .collect()
+}
+
+// Run `command` from `dir`. A missing directory is reported (cd prints
+// why) but the command still starts, from wherever the container put us.
+pub fn in_workdir(dir: &str, command: &str) -> String {
+    let body = format!(
+        "cd {} || echo \"ecs_remote: staying in $PWD\" >&2; exec {}",
+        quote(dir),
+        command
+    );
+    format!("sh -c {}", quote(&body))
+}
+
+// Run `command` as `user` (a name or uid) via whichever of `runuser`,
+// `setpriv` or `su` the image has. This is best-effort: images without any
+// of them get an explanatory error instead of a shell.
+pub fn as_user(name: &str, command: &str) -> String {
+    let user = quote(name);
+    let inner = quote(command);
+    let body = format!(
+        concat!(
+            "if command -v runuser >/dev/null 2>&1; then exec runuser -u {user} -- sh -c {inner}; ",
+            "elif command -v setpriv >/dev/null 2>&1; then ",
+            "exec setpriv --reuid={user} --regid=\"$(id -g {user})\" --init-groups sh -c {inner}; ",
+            "elif command -v su >/dev/null 2>&1; then exec su -s /bin/sh {user} -c {inner}; ",
+            "else echo {missing} >&2; exit 127; fi"
+        ),
+        user = user,
+        inner = inner,
+        missing = quote(&format!(
+            "ecs_remote: cannot switch to user {}: none of runuser, setpriv or su is installed in the container",
+            name
+        ))
+    );
+    format!("sh -c {}", quote(&body))
+}
+
+// Prefix `command` with `env KEY=VALUE ...` so it starts with `vars` set.
+pub fn with_env(vars: &[(String, String)], command: &str) -> String {
+    if vars.is_empty() {
+        return command.to_string();
+    }
+    let assignments: Vec<String> = vars
+        .iter()
+        .map(|(key, value)| quote(&format!("{}={}", key, value)))
+        .collect();
+    format!("env {} {}", assignments.join(" "), command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Values that break naive quoting
+    const NASTY: &[&str] = &[
+        "",
+        "two words",
+        "it's",
+        "'",
+        "''",
+        "say \"hi\"",
+        r"back\slash",
+        r"trailing\",
+        "line1\nline2",
+        "$HOME",
+        "$(touch /tmp/ecs_remote-pwned)",
+        "`id`",
+        "a;b && c | d > e",
+        "*",
+        "~",
+        "#not a comment",
+        "tab\there",
+        "ünïcødé",
+    ];
+
+    // Run `command` the way the exec agent does: split into words, the
+    // first being the program
+    #[cfg(unix)]
+    fn run(command: &str) -> String {
+        let argv = shell_words::split(command).unwrap();
+        let output = std::process::Command::new(&argv[0])
+            .args(&argv[1..])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?} failed: {:?}", argv, output);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn quote_leaves_plain_words_bare() {
+        assert_eq!(quote("app"), "app");
+        assert_eq!(quote("/app/bin"), "/app/bin");
+        assert_eq!(quote("KEY=value"), "KEY=value");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn quote_is_one_word() {
+        for value in NASTY {
+            assert_eq!(
+                shell_words::split(&quote(value)).unwrap(),
+                vec![value.to_string()],
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quote_survives_sh() {
+        for value in NASTY {
+            let script = format!("printf %s {}", quote(value));
+            assert_eq!(run(&format!("sh -c {}", quote(&script))), *value);
+        }
+        assert!(!Path::new("/tmp/ecs_remote-pwned").exists());
+    }
+
+    // Read back a PowerShell verbatim string, taking every kind of single
+    // quote for a quote as PowerShell does
+    fn unquote_powershell(word: &str) -> String {
+        let is_quote =
+            |c: char| matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}');
+        let mut chars = word.chars();
+        if !chars.next().is_some_and(is_quote) {
+            return word.to_string();
+        }
+        let mut value = String::new();
+        let mut chars = chars.peekable();
+        while let Some(c) = chars.next() {
+            if is_quote(c) {
+                match chars.next() {
+                    Some(next) if is_quote(next) => value.push(next),
+                    None => return value,
+                    Some(next) => panic!("{:?} ends early, before {:?}", word, next),
+                }
+            } else {
+                value.push(c);
+            }
+        }
+        panic!("{:?} is not terminated", word)
+    }
+
+    #[test]
+    fn quote_powershell_round_trips() {
+        let typographic = ["‘left’", "it’s", "‚low‛", "mixed '‘’ quotes"];
+        for value in NASTY.iter().chain(&typographic) {
+            let quoted = quote_powershell(value);
+            assert_eq!(unquote_powershell(&quoted), *value, "{}", quoted);
+        }
+        assert_eq!(quote_powershell(r"C:\app"), r"C:\app");
+        assert_eq!(quote_powershell("$env:PATH"), "'$env:PATH'");
+        assert_eq!(quote_powershell("it’s"), "'it’’s'");
+    }
+
+    #[test]
+    fn powershell_encodes_the_statements() {
+        let vars = vec![("NOTE".to_string(), "it’s $x".to_string())];
+        let command = powershell(Some(r"C:\my app"), &vars, Some("& 'dir'"));
+        let encoded = command
+            .strip_prefix("powershell.exe -NoProfile -EncodedCommand ")
+            .unwrap();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        let utf16: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(
+            String::from_utf16(&utf16).unwrap(),
+            r"Set-Location -LiteralPath 'C:\my app'; $env:NOTE = 'it’’s $x'; & 'dir'"
+        );
+        assert_eq!(powershell(None, &[], None), POWERSHELL);
+        assert!(powershell(Some("C:/app"), &[], None).contains(" -NoExit "));
+    }
+
+    #[test]
+    fn powershell_argv_quotes_each_word() {
+        let words = [
+            "cmd.exe".to_string(),
+            "/c".to_string(),
+            "echo it's".to_string(),
+        ];
+        assert_eq!(powershell_argv(&words), "& cmd.exe /c 'echo it''s'");
+    }
+
+    // A file under the temp directory, unique to this process
+    fn temp_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("ecs_remote-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn parse_env_takes_the_value_verbatim() {
+        let cases = [
+            ("A=b=c", "b=c"),
+            ("QUOTED='x y'", "'x y'"),
+            ("DOUBLE=\"x\"", "\"x\""),
+            ("DOLLAR=$HOME", "$HOME"),
+            ("MULTI=line1\nline2", "line1\nline2"),
+            ("EMPTY=", ""),
+            ("_UNDER9=1", "1"),
+        ];
+        for (spec, value) in cases {
+            let (key, parsed) = parse_env(spec).unwrap();
+            assert_eq!(format!("{}={}", key, parsed), spec);
+            assert_eq!(parsed, value);
+        }
+        for spec in ["NOEQUALS", "=value", "1A=x", "A-B=x", "A B=x"] {
+            assert!(parse_env(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn read_env_file_skips_comments_and_export() {
+        let path = temp_file(
+            "env",
+            "# a comment\n\nDEBUG=1\n  # indented comment\nexport REGION=eu-west-1\nexport EMPTY=\nURL=http://x/?a=b#frag\n",
+        );
+        let vars = read_env_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected = [
+            ("DEBUG", "1"),
+            ("REGION", "eu-west-1"),
+            ("EMPTY", ""),
+            ("URL", "http://x/?a=b#frag"),
+        ];
+        assert_eq!(
+            vars,
+            expected.map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn read_env_file_names_the_bad_line() {
+        let path = temp_file("bad-env", "GOOD=1\nnot a pair\n");
+        let err = read_env_file(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(format!("{:#}", err).contains(":2"), "{:#}", err);
+        assert!(read_env_file(Path::new("/nonexistent/ecs_remote.env")).is_err());
+    }
+
+    #[test]
+    fn with_env_without_vars_is_the_command() {
+        assert_eq!(with_env(&[], "/bin/bash"), "/bin/bash");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn with_env_survives_nasty_values() {
+        for value in NASTY {
+            let vars = [("V".to_string(), value.to_string())];
+            let command = format!("sh -c {}", quote("printf %s \"$V\""));
+            assert_eq!(run(&with_env(&vars, &command)), *value);
+        }
+    }
+
+    // Run `command` like `run` with only `dir` on the PATH; returns the exit
+    // code, stdout and stderr
+    #[cfg(unix)]
+    fn run_with_path(command: &str, dir: &Path) -> (Option<i32>, String, String) {
+        let argv = shell_words::split(command).unwrap();
+        let output = std::process::Command::new(dir.join(&argv[0]))
+            .args(&argv[1..])
+            .env("PATH", dir)
+            .output()
+            .unwrap();
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    }
+
+    // A PATH directory with `sh`, `env` and the given stand-ins for the
+    // user switching tools, which report the user and run the command as is
+    #[cfg(unix)]
+    fn stub_path(name: &str, tools: &[&str]) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("ecs_remote-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (link, target) in [("sh", "/bin/sh"), ("env", "/usr/bin/env")] {
+            let _ = std::os::unix::fs::symlink(target, dir.join(link));
+        }
+        for tool in tools {
+            // runuser -u USER -- sh -c CMD, su -s /bin/sh USER -c CMD
+            let body = match *tool {
+                "runuser" => "echo \"runuser as $2\" >&2; shift 3; exec \"$@\"",
+                "su" => "echo \"su as $3\" >&2; exec /bin/sh -c \"$5\"",
+                other => panic!("no stub for {}", other),
+            };
+            let path = dir.join(tool);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        dir
+    }
+
+    // --user, --workdir and --env wrapped around a shell, as `remote_command`
+    // composes them
+    #[cfg(unix)]
+    fn session(user: &str, dir: &str, value: &str) -> String {
+        let vars = [("V".to_string(), value.to_string())];
+        let inner = format!("sh -c {}", quote("pwd; printf %s \"$V\""));
+        with_env(&vars, &in_workdir(dir, &as_user(user, &inner)))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nested_quoting_survives_every_layer() {
+        let workdir =
+            std::env::temp_dir().join(format!("ecs_remote-{} my 'dir'", std::process::id()));
+        std::fs::create_dir_all(&workdir).unwrap();
+        let workdir = workdir.canonicalize().unwrap();
+        let dir = workdir.to_str().unwrap();
+        for tool in ["runuser", "su"] {
+            let path = stub_path(tool, &[tool]);
+            for value in NASTY {
+                let (code, stdout, stderr) = run_with_path(&session("app user", dir, value), &path);
+                assert_eq!(code, Some(0), "{}: {}", tool, stderr);
+                assert_eq!(stdout, format!("{}\n{}", dir, value), "{}", tool);
+                assert_eq!(stderr, format!("{} as app user\n", tool));
+            }
+            std::fs::remove_dir_all(&path).unwrap();
+        }
+        std::fs::remove_dir_all(&workdir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn as_user_explains_missing_tools() {
+        let path = stub_path("no-tools", &[]);
+        let (code, stdout, stderr) = run_with_path(&session("app", "/", "x"), &path);
+        std::fs::remove_dir_all(&path).unwrap();
+        assert_eq!(code, Some(127));
+        assert_eq!(stdout, "");
+        assert!(
+            stderr.contains("cannot switch to user app: none of runuser, setpriv or su"),
+            "{}",
+            stderr
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn in_workdir_starts_anyway_when_the_directory_is_missing() {
+        let command = in_workdir("/nonexistent dir", &format!("sh -c {}", quote("printf ok")));
+        let path = stub_path("workdir", &[]);
+        let (code, stdout, stderr) = run_with_path(&command, &path);
+        std::fs::remove_dir_all(&path).unwrap();
+        assert_eq!((code, stdout.as_str()), (Some(0), "ok"));
+        assert!(stderr.contains("ecs_remote: staying in"), "{}", stderr);
+    }
+}
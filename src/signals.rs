@@ -0,0 +1,228 @@
+//! Passing termination signals on to the sessions.
+
+use std::process::{Child, ExitStatus};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// How long the session gets to close after the signal
+const GRACE: Duration = Duration::from_secs(5);
+
+// How often to look whether a session we wait on with a limit has ended
+const WAIT_POLL: Duration = Duration::from_millis(100);
+
+// Process IDs of the open sessions' CLIs
+static SESSIONS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+static WATCHING: OnceLock<()> = OnceLock::new();
+
+// The signal that ended the session, once one has
+static RECEIVED: OnceLock<&'static str> = OnceLock::new();
+
+// Forward termination signals to `child` until the returned guard is
+// dropped (once the child has been waited for).
+pub fn forward_to(child: &Child) -> SessionGuard {
+    WATCHING.get_or_init(watch);
+    sessions().push(child.id());
+    SessionGuard(child.id())
+}
+
+pub struct SessionGuard(u32);
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        sessions().retain(|&pid| pid != self.0);
+    }
+}
+
+fn sessions() -> std::sync::MutexGuard<'static, Vec<u32>> {
+    SESSIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// For the rest of the run, end the open sessions on Ctrl-C and keep going,
+// so a batch can stop starting new ones and sum up.
+pub fn stop_on_interrupt() {
+    tokio::spawn(async {
+        if interrupted().await.is_err() || RECEIVED.set("Ctrl-C").is_err() {
+            return;
+        }
+        let pids = sessions().clone();
+        eprintln!("Interrupted, ending {} running session(s)", pids.len());
+        // The terminal sent every session the interrupt already, but the
+        // plugin may pass it on to the remote command instead of exiting
+        for &pid in &pids {
+            end(pid);
+        }
+        outlast(&pids, "Ctrl-C").await;
+    });
+}
+
+#[cfg(unix)]
+async fn interrupted() -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    signal(SignalKind::interrupt())?.recv().await;
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn interrupted() -> std::io::Result<()> {
+    tokio::signal::windows::ctrl_c()?.recv().await;
+    Ok(())
+}
+
+// Give the sessions the grace period, then kill the ones still open
+async fn outlast(pids: &[u32], name: &str) {
+    tokio::time::sleep(GRACE).await;
+    for &pid in pids {
+        if sessions().contains(&pid) {
+            eprintln!(
+                "The session did not end within {}s of {}; killing it",
+                GRACE.as_secs(),
+                name
+            );
+            kill(pid);
+        }
+    }
+}
+
+// Wait up to `limit` for `child` to exit; None when it is still running.
+pub fn wait_within(child: &mut Child, limit: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + limit;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(WAIT_POLL);
+    }
+}
+
+// End `child`'s session because of `why` (a timeout): terminated, and
+// killed when it is still open after the grace period. Blocks until it has
+// exited.
+pub fn stop(child: &mut Child, why: &str) -> std::io::Result<ExitStatus> {
+    end(child.id());
+    if let Some(status) = wait_within(child, GRACE)? {
+        return Ok(status);
+    }
+    eprintln!(
+        "The session did not end within {}s of {}; killing it",
+        GRACE.as_secs(),
+        why
+    );
+    child.kill()?;
+    child.wait()
+}
+
+// The signal that ended the session, e.g. `SIGTERM`, if one did.
+pub fn received() -> Option<&'static str> {
+    RECEIVED.get().copied()
+}
+
+// What to do about a termination signal: pass it on to the open session,
+// or exit as the signal would have made us
+async fn terminate(name: &'static str, code: i32) {
+    let pids = sessions().clone();
+    if pids.is_empty() && received().is_none() {
+        eprintln!("Terminated by {}", name);
+        std::process::exit(128 + code);
+    }
+    // Already ending, or our own signal coming back through the group
+    if RECEIVED.set(name).is_err() {
+        return;
+    }
+    eprintln!("Received {}, ending the session", name);
+    pass_on(&pids, code);
+    outlast(&pids, name).await;
+}
+
+#[cfg(unix)]
+fn watch() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let signals = [
+        (SignalKind::terminate(), "SIGTERM", libc::SIGTERM),
+        (SignalKind::hangup(), "SIGHUP", libc::SIGHUP),
+        (SignalKind::quit(), "SIGQUIT", libc::SIGQUIT),
+    ];
+    for (kind, name, code) in signals {
+        let Ok(mut stream) = signal(kind) else {
+            continue;
+        };
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                terminate(name, code).await;
+            }
+        });
+    }
+}
+
+// The plugin runs under the CLI, so when the process group is ours alone
+// the whole group gets the signal; otherwise (started by a script without
+// job control) only the CLIs do
+#[cfg(unix)]
+fn pass_on(pids: &[u32], code: i32) {
+    // SAFETY: plain system calls without pointers
+    unsafe {
+        if libc::getpgrp() == libc::getpid() {
+            libc::kill(-libc::getpgrp(), code);
+        } else {
+            for &pid in pids {
+                libc::kill(pid as libc::pid_t, code);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn end(pid: u32) {
+    // SAFETY: a plain system call without pointers
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+}
+
+#[cfg(unix)]
+fn kill(pid: u32) {
+    // SAFETY: a plain system call without pointers
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(windows)]
+fn watch() {
+    use tokio::signal::windows::{ctrl_close, ctrl_logoff, ctrl_shutdown};
+    macro_rules! listen {
+        ($event:expr, $name:expr) => {
+            if let Ok(mut stream) = $event {
+                tokio::spawn(async move {
+                    while stream.recv().await.is_some() {
+                        terminate($name, 1).await;
+                    }
+                });
+            }
+        };
+    }
+    listen!(ctrl_close(), "console close");
+    listen!(ctrl_logoff(), "logoff");
+    listen!(ctrl_shutdown(), "shutdown");
+}
+
+// Every process on the console gets the event itself
+#[cfg(windows)]
+fn pass_on(_pids: &[u32], _code: i32) {}
+
+#[cfg(windows)]
+fn end(pid: u32) {
+    kill(pid);
+}
+
+#[cfg(windows)]
+fn kill(pid: u32) {
+    let _ = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .output();
+}
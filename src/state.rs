@@ -0,0 +1,460 @@
+//! Persistent state kept between runs, written under a lock.
+
+use crate::json::{self, Value};
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "history.json";
+const HISTORY_LIMIT: usize = 20;
+const FAVORITES_FILE: &str = "favorites.json";
+const CONTEXTS_FILE: &str = "contexts.json";
+const CHOICES_FILE: &str = "choices.json";
+
+// Resolve the state directory, honoring `ECS_REMOTE_STATE_DIR` and the XDG
+// conventions before falling back to `~/.local/state/ecs_remote`.
+pub fn state_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("ECS_REMOTE_STATE_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME") {
+        return Ok(PathBuf::from(dir).join("ecs_remote"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+            return Ok(PathBuf::from(dir).join("ecs_remote"));
+        }
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .context("Cannot determine home directory for the state directory")?;
+    Ok(PathBuf::from(home)
+        .join(".local")
+        .join("state")
+        .join("ecs_remote"))
+}
+
+pub fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+// Read a JSON state file, treating a missing or corrupt file as `Null`.
+pub fn read(name: &str) -> Result<Value> {
+    let path = state_dir()?.join(name);
+    let _lock = lock(name)?;
+    Ok(read_unlocked(&path))
+}
+
+// Apply `update` to the current contents of a state file and write the
+// result back, all while holding the file's lock.
+pub fn update<T>(name: &str, update: impl FnOnce(&mut Value) -> T) -> Result<T> {
+    let dir = state_dir()?;
+    let path = dir.join(name);
+    let _lock = lock(name)?;
+
+    let mut value = read_for_update(&path)?;
+    let result = update(&mut value);
+
+    let tmp = dir.join(format!("{}.tmp", name));
+    fs::write(&tmp, value.to_pretty())
+        .with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(result)
+}
+
+fn read_unlocked(path: &PathBuf) -> Value {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| json::parse(&contents).ok())
+        .unwrap_or(Value::Null)
+}
+
+// What `update` starts from. Unlike reading, it must not take a corrupt
+// file for an empty one and write over it, so that file is moved aside to
+// `<name>.corrupt` first.
+fn read_for_update(path: &Path) -> Result<Value> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Value::Null),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    if contents.trim().is_empty() {
+        return Ok(Value::Null);
+    }
+    match json::parse(&contents) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            let mut aside = path.as_os_str().to_owned();
+            aside.push(".corrupt");
+            let aside = PathBuf::from(aside);
+            fs::rename(path, &aside)
+                .with_context(|| format!("Failed to move aside {}", path.display()))?;
+            eprintln!(
+                "Warning: {} was not valid JSON ({}); moved it to {} and started a new one",
+                path.display(),
+                err,
+                aside.display()
+            );
+            Ok(Value::Null)
+        }
+    }
+}
+
+fn lock(name: &str) -> Result<File> {
+    let dir = state_dir()?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create state directory {}", dir.display()))?;
+    let path = dir.join(format!("{}.lock", name));
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", path.display()))?;
+    Ok(file)
+}
+
+// Everything needed to reconnect to a service without prompting.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub profile: String,
+    pub region: Option<String>,
+    pub cluster_arn: String,
+    pub service_name: String,
+    pub container: String,
+    pub shell: Option<String>,
+}
+
+impl Target {
+    fn write_json(&self, value: &mut Value) {
+        value.set("profile", &self.profile);
+        if let Some(region) = &self.region {
+            value.set("region", region);
+        }
+        value.set("cluster_arn", &self.cluster_arn);
+        value.set("service_name", &self.service_name);
+        value.set("container", &self.container);
+        if let Some(shell) = &self.shell {
+            value.set("shell", shell);
+        }
+    }
+
+    fn from_json(value: &Value) -> Option<Target> {
+        Some(Target {
+            profile: value.str_field("profile")?,
+            region: value.str_field("region"),
+            cluster_arn: value.str_field("cluster_arn")?,
+            service_name: value.str_field("service_name")?,
+            container: value.str_field("container")?,
+            shell: value.str_field("shell"),
+        })
+    }
+
+    fn same_as(&self, other: &Target) -> bool {
+        self.profile == other.profile
+            && self.region == other.region
+            && self.cluster_arn == other.cluster_arn
+            && self.service_name == other.service_name
+            && self.container == other.container
+    }
+}
+
+// A previously successful connection.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub target: Target,
+    pub task_family: String,
+    // The task connected to; entries from before it was recorded lack it
+    pub task_id: Option<String>,
+    pub timestamp: i64,
+}
+
+impl HistoryEntry {
+    fn to_json(&self) -> Value {
+        let mut value = Value::object();
+        self.target.write_json(&mut value);
+        value.set("task_family", &self.task_family);
+        if let Some(task_id) = &self.task_id {
+            value.set("task_id", task_id);
+        }
+        value.set("timestamp", self.timestamp);
+        value
+    }
+
+    fn from_json(value: &Value) -> Option<HistoryEntry> {
+        Some(HistoryEntry {
+            target: Target::from_json(value)?,
+            task_family: value.str_field("task_family").unwrap_or_default(),
+            task_id: value.str_field("task_id"),
+            timestamp: value.get("timestamp").and_then(Value::as_i64).unwrap_or(0),
+        })
+    }
+}
+
+fn entries_of(value: &Value) -> Vec<HistoryEntry> {
+    value
+        .as_array()
+        .map(|items| items.iter().filter_map(HistoryEntry::from_json).collect())
+        .unwrap_or_default()
+}
+
+// Connection history, newest first.
+pub fn history() -> Result<Vec<HistoryEntry>> {
+    Ok(entries_of(&read(HISTORY_FILE)?))
+}
+
+// Record a connection, replacing any older entry for the same target and
+// keeping only the most recent `HISTORY_LIMIT` entries.
+pub fn record_history(entry: HistoryEntry) -> Result<()> {
+    update(HISTORY_FILE, |value| {
+        let mut entries = entries_of(value);
+        entries.retain(|e| !e.target.same_as(&entry.target));
+        entries.insert(0, entry);
+        entries.truncate(HISTORY_LIMIT);
+        *value = Value::Array(entries.iter().map(HistoryEntry::to_json).collect());
+    })
+}
+
+// Drop every entry for `target`.
+pub fn remove_history(target: &Target) -> Result<()> {
+    update(HISTORY_FILE, |value| {
+        let mut entries = entries_of(value);
+        entries.retain(|e| !e.target.same_as(target));
+        *value = Value::Array(entries.iter().map(HistoryEntry::to_json).collect());
+    })
+}
+
+pub fn clear_history() -> Result<()> {
+    update(HISTORY_FILE, |value| *value = Value::Array(Vec::new()))
+}
+
+// Favorites in the order they were added.
+pub fn favorites() -> Result<Vec<(String, Target)>> {
+    Ok(favorites_of(&read(FAVORITES_FILE)?))
+}
+
+fn favorites_of(value: &Value) -> Vec<(String, Target)> {
+    match value {
+        Value::Object(entries) => entries
+            .iter()
+            .filter_map(|(name, v)| Target::from_json(v).map(|t| (name.clone(), t)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn favorite(name: &str) -> Result<Option<Target>> {
+    Ok(favorites()?
+        .into_iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, t)| t))
+}
+
+// Save a favorite, replacing one of the same name only when `replace`;
+// returns whether it was saved. Ask before calling, not while the lock is
+// held, so other shells aren't kept waiting on the answer.
+pub fn save_favorite(name: &str, target: &Target, replace: bool) -> Result<bool> {
+    update(FAVORITES_FILE, |value| {
+        if !matches!(value, Value::Object(_)) {
+            *value = Value::object();
+        }
+        if value.get(name).is_some() && !replace {
+            return false;
+        }
+        let mut entry = Value::object();
+        target.write_json(&mut entry);
+        value.set(name, entry);
+        true
+    })
+}
+
+// Delete a favorite; returns whether it existed.
+pub fn remove_favorite(name: &str) -> Result<bool> {
+    update(FAVORITES_FILE, |value| match value {
+        Value::Object(entries) => {
+            let before = entries.len();
+            entries.retain(|(n, _)| n != name);
+            entries.len() != before
+        }
+        _ => false,
+    })
+}
+
+// Named defaults (profile, cluster, region) selected with `context use`.
+#[derive(Debug, Clone, Default)]
+pub struct SavedContext {
+    pub profile: Option<String>,
+    pub cluster: Option<String>,
+    pub region: Option<String>,
+}
+
+impl SavedContext {
+    fn to_json(&self) -> Value {
+        let mut value = Value::object();
+        for (key, field) in [
+            ("profile", &self.profile),
+            ("cluster", &self.cluster),
+            ("region", &self.region),
+        ] {
+            if let Some(field) = field {
+                value.set(key, field);
+            }
+        }
+        value
+    }
+
+    fn from_json(value: &Value) -> SavedContext {
+        SavedContext {
+            profile: value.str_field("profile"),
+            cluster: value.str_field("cluster"),
+            region: value.str_field("region"),
+        }
+    }
+}
+
+// Every context in the order they were created.
+pub fn contexts() -> Result<Vec<(String, SavedContext)>> {
+    Ok(match read(CONTEXTS_FILE)?.get("contexts") {
+        Some(Value::Object(entries)) => entries
+            .iter()
+            .map(|(name, v)| (name.clone(), SavedContext::from_json(v)))
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+pub fn current_context_name() -> Result<Option<String>> {
+    Ok(read(CONTEXTS_FILE)?.str_field("current"))
+}
+
+// The current context, if one is set and still defined.
+pub fn current_context() -> Result<Option<(String, SavedContext)>> {
+    let Some(name) = current_context_name()? else {
+        return Ok(None);
+    };
+    Ok(contexts()?.into_iter().find(|(n, _)| *n == name))
+}
+
+// Create a context or update the fields that are set in `context`.
+pub fn save_context(name: &str, context: &SavedContext) -> Result<()> {
+    update(CONTEXTS_FILE, |value| {
+        if !matches!(value, Value::Object(_)) {
+            *value = Value::object();
+        }
+        let mut merged = value
+            .get("contexts")
+            .and_then(|c| c.get(name))
+            .map(SavedContext::from_json)
+            .unwrap_or_default();
+        merged.profile = context.profile.clone().or(merged.profile);
+        merged.cluster = context.cluster.clone().or(merged.cluster);
+        merged.region = context.region.clone().or(merged.region);
+
+        let mut contexts = match value.get("contexts") {
+            Some(c @ Value::Object(_)) => c.clone(),
+            _ => Value::object(),
+        };
+        contexts.set(name, merged.to_json());
+        value.set("contexts", contexts);
+    })
+}
+
+// Make `name` the current context (or none); returns false when no such
+// context exists.
+pub fn use_context(name: Option<&str>) -> Result<bool> {
+    update(CONTEXTS_FILE, |value| {
+        if !matches!(value, Value::Object(_)) {
+            *value = Value::object();
+        }
+        match name {
+            Some(name) => {
+                if value.get("contexts").and_then(|c| c.get(name)).is_none() {
+                    return false;
+                }
+                value.set("current", name);
+            }
+            None => {
+                if let Value::Object(entries) = value {
+                    entries.retain(|(k, _)| k != "current");
+                }
+            }
+        }
+        true
+    })
+}
+
+// What was chosen last time in the picker `picker` (`cluster`,
+// `service:<cluster ARN>`, ...) under `profile`: an ARN or name, never an
+// index, so a reordered list still finds it. A missing or corrupt file is
+// no choice.
+pub fn last_choice(profile: &str, picker: &str) -> Option<String> {
+    read(CHOICES_FILE).ok()?.get(profile)?.str_field(picker)
+}
+
+// Remember `choice` for the picker `picker` under `profile`.
+pub fn remember_choice(profile: &str, picker: &str, choice: &str) -> Result<()> {
+    update(CHOICES_FILE, |value| {
+        if !matches!(value, Value::Object(_)) {
+            *value = Value::object();
+        }
+        let mut choices = value
+            .get(profile)
+            .filter(|choices| matches!(choices, Value::Object(_)))
+            .cloned()
+            .unwrap_or_else(Value::object);
+        choices.set(picker, choice);
+        value.set(profile, choices);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, contents: Option<&str>) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ecs_remote-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        if let Some(contents) = contents {
+            fs::write(&path, contents).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn update_starts_from_missing_or_empty_files() {
+        let missing = temp_file("state-missing.json", None);
+        assert!(matches!(read_for_update(&missing).unwrap(), Value::Null));
+        let empty = temp_file("state-empty.json", Some("\n"));
+        assert!(matches!(read_for_update(&empty).unwrap(), Value::Null));
+        let _ = fs::remove_file(empty);
+    }
+
+    #[test]
+    fn update_reads_valid_files() {
+        let path = temp_file("state-valid.json", Some(r#"{"db": {"profile": "uat"}}"#));
+        let value = read_for_update(&path).unwrap();
+        assert!(value.get("db").is_some());
+        assert!(path.exists());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn update_moves_corrupt_files_aside() {
+        let contents = r#"{"db": {"profile": "uat""#;
+        let path = temp_file("state-corrupt.json", Some(contents));
+        let aside = temp_file("state-corrupt.json.corrupt", None);
+        assert!(matches!(read_for_update(&path).unwrap(), Value::Null));
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(&aside).unwrap(), contents);
+        let _ = fs::remove_file(aside);
+    }
+}
@@ -0,0 +1,82 @@
+//! A custom CA bundle, for TLS-inspecting proxies with an internal CA.
+
+use crate::failure::Kind;
+use anyhow::Result;
+use rustls::{Certificate, ClientConfig, RootCertStore};
+use std::io::BufReader;
+use std::process::Command;
+use std::sync::OnceLock;
+
+pub const ENV_VAR: &str = "AWS_CA_BUNDLE";
+
+static BUNDLE: OnceLock<Option<Bundle>> = OnceLock::new();
+
+struct Bundle {
+    path: String,
+    config: ClientConfig,
+    // Set with --ca-bundle or the config file, so the AWS CLI has to be told
+    forward: bool,
+}
+
+// Load the bundle from `flag`, the environment or `configured`, in that
+// order. Returns what `--verbose` reports: the file and how many
+// certificates it has.
+pub fn configure(flag: Option<&str>, configured: Option<String>) -> Result<Option<String>> {
+    let chosen = match (flag, std::env::var(ENV_VAR).ok().filter(|v| !v.is_empty())) {
+        (Some(path), _) => Some((path.to_string(), true)),
+        (None, Some(path)) => Some((path, false)),
+        (None, None) => configured.map(|path| (path, true)),
+    };
+    let Some((path, forward)) = chosen else {
+        let _ = BUNDLE.set(None);
+        return Ok(None);
+    };
+    let (config, count) = load(&path)?;
+    let _ = BUNDLE.set(Some(Bundle {
+        path: path.clone(),
+        config,
+        forward,
+    }));
+    Ok(Some(format!("{} ({} certificates)", path, count)))
+}
+
+fn load(path: &str) -> Result<(ClientConfig, usize)> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| Kind::Usage.error(format!("Cannot read CA bundle {}: {}", path, err)))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|err| Kind::Usage.error(format!("Invalid PEM in CA bundle {}: {}", path, err)))?;
+    if certs.is_empty() {
+        return Err(Kind::Usage.error(format!("CA bundle {} has no PEM certificates", path)));
+    }
+    let mut roots = RootCertStore::empty();
+    for (i, cert) in certs.iter().enumerate() {
+        roots.add(&Certificate(cert.clone())).map_err(|err| {
+            Kind::Usage.error(format!(
+                "Certificate {} in CA bundle {} is invalid: {}",
+                i + 1,
+                path,
+                err
+            ))
+        })?;
+    }
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok((config, certs.len()))
+}
+
+// The TLS configuration trusting only the bundle, if there is one.
+pub fn client_config() -> Option<ClientConfig> {
+    BUNDLE.get()?.as_ref().map(|bundle| bundle.config.clone())
+}
+
+// Hand the bundle on to an AWS CLI process; `AWS_CA_BUNDLE` reaches it by
+// itself.
+pub fn apply(cli: &mut Command) {
+    if let Some(Some(bundle)) = BUNDLE.get() {
+        if bundle.forward {
+            cli.args(["--ca-bundle", &bundle.path]);
+        }
+    }
+}
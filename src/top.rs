@@ -0,0 +1,238 @@
+//! `top`: a refreshing table of a service's tasks.
+
+use crate::output::Table;
+use crate::{
+    build_client, choose_service, fetch_metrics, format_age, list_valid_tasks, open_session,
+    picker, sort_tasks, state, Args, Selection, Session, TaskInfo,
+};
+use anyhow::Result;
+use console::{style, Key, Term};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const REFRESH_SECS: u64 = 10;
+
+const HEADERS: [&str; 6] = ["TASK", "HEALTH", "UP", "REV", "CPU", "MEM"];
+// Uptime changes on every refresh, so it is never highlighted
+const UPTIME: usize = 2;
+
+// One refresh worth of data
+struct Frame {
+    tasks: Vec<TaskInfo>,
+    not_ready: usize,
+    // Why there are no metrics, if there aren't
+    note: Option<String>,
+}
+
+pub async fn run(args: &Args) -> Result<()> {
+    let client = build_client(&args.profile, args.region.as_deref()).await;
+    let (cluster_arn, service, listing) = choose_service(args, &client).await?;
+    let service_name = service.service_name.clone();
+    let mut frame = Frame {
+        tasks: listing.tasks,
+        not_ready: listing.rejected.len(),
+        note: None,
+    };
+    sort_tasks(&mut frame.tasks, args);
+    frame.note = fetch_metrics(args, &cluster_arn, &mut frame.tasks).await;
+
+    let term = Term::stdout();
+    if !term.is_term() || !picker::is_interactive() {
+        for line in render(&frame, &HashMap::new(), None, None) {
+            println!("{}", line);
+        }
+        println!("{}", footer(&service_name, &frame, false));
+        return Ok(());
+    }
+
+    term.hide_cursor()?;
+    let chosen = watch(args, &client, &term, &cluster_arn, &service_name, frame).await;
+    term.show_cursor()?;
+    let Some(task) = chosen? else {
+        return Ok(());
+    };
+    let selection = Selection {
+        cluster_arn,
+        service,
+        task,
+        container: None,
+    };
+    open_session(args, &client, &Session::from_args(args)?, selection).await
+}
+
+// Redraw until a task is picked (Some) or the user quits (None)
+async fn watch(
+    args: &Args,
+    client: &aws_sdk_ecs::Client,
+    term: &Term,
+    cluster_arn: &str,
+    service_name: &str,
+    mut frame: Frame,
+) -> Result<Option<TaskInfo>> {
+    let mut keys = read_keys();
+    let mut previous: HashMap<String, Vec<String>> = HashMap::new();
+    let mut selected: Option<String> = None;
+    let mut drawn = 0;
+    loop {
+        let mut cursor = selected
+            .as_ref()
+            .and_then(|id| frame.tasks.iter().position(|task| &task.task_id == id))
+            .unwrap_or(0);
+        let (_, cols) = term.size();
+        let deadline = Instant::now() + Duration::from_secs(REFRESH_SECS);
+        loop {
+            term.clear_last_lines(drawn)?;
+            let mut lines = render(&frame, &previous, Some(cursor), Some(cols as usize));
+            lines.push(footer(service_name, &frame, true));
+            for line in &lines {
+                term.write_line(line)?;
+            }
+            drawn = lines.len();
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match tokio::task::block_in_place(|| keys.recv_timeout(timeout)) {
+                Err(RecvTimeoutError::Timeout) => break,
+                // The reader stops after Enter; start it again
+                Err(RecvTimeoutError::Disconnected) => keys = read_keys(),
+                Ok(Key::ArrowUp) => cursor = cursor.saturating_sub(1),
+                Ok(Key::ArrowDown) if cursor + 1 < frame.tasks.len() => cursor += 1,
+                Ok(Key::Enter) if !frame.tasks.is_empty() => {
+                    term.clear_last_lines(drawn)?;
+                    return Ok(Some(frame.tasks[cursor].clone()));
+                }
+                Ok(Key::Char('q')) | Ok(Key::Escape) | Ok(Key::CtrlC) => {
+                    term.clear_last_lines(drawn)?;
+                    return Ok(None);
+                }
+                Ok(_) => {}
+            }
+        }
+        selected = frame.tasks.get(cursor).map(|task| task.task_id.clone());
+        previous = frame
+            .tasks
+            .iter()
+            .map(|task| (task.task_id.clone(), cells(task)))
+            .collect();
+
+        let listing = list_valid_tasks(
+            client,
+            cluster_arn,
+            service_name,
+            args.max_results,
+            args.max_tasks,
+        )
+        .await?;
+        let mut tasks = listing.tasks;
+        sort_tasks(&mut tasks, args);
+        let note = fetch_metrics(args, cluster_arn, &mut tasks).await;
+        frame = Frame {
+            tasks,
+            not_ready: listing.rejected.len(),
+            note,
+        };
+    }
+}
+
+// Keys pressed, read on a thread of their own so the table keeps
+// refreshing. The thread ends after a key that leaves the view, so nothing
+// competes with the session for the terminal.
+fn read_keys() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let term = Term::stdout();
+        while let Ok(key) = term.read_key() {
+            let last = matches!(key, Key::Enter | Key::Escape | Key::CtrlC | Key::Char('q'));
+            if sender.send(key).is_err() || last {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn cells(task: &TaskInfo) -> Vec<String> {
+    let usage = task.usage.unwrap_or_default();
+    let percent = |value: Option<f64>| {
+        value
+            .map(|value| format!("{:.0}%", value))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    vec![
+        task.task_id.clone(),
+        task.health.clone(),
+        task.started_at
+            .map(|started| {
+                format_age(state::now_secs() - started)
+                    .trim_end_matches(" ago")
+                    .to_string()
+            })
+            .unwrap_or_else(|| "-".to_string()),
+        task.revision
+            .map(|revision| revision.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+        percent(usage.cpu),
+        percent(usage.memory),
+    ]
+}
+
+// The table, with changed cells in yellow and new tasks in green, and a
+// marker in front of the row under the cursor
+fn render(
+    frame: &Frame,
+    previous: &HashMap<String, Vec<String>>,
+    cursor: Option<usize>,
+    cols: Option<usize>,
+) -> Vec<String> {
+    let mut table = Table::new(&HEADERS);
+    for task in &frame.tasks {
+        let old = previous.get(&task.task_id);
+        let row = cells(task)
+            .into_iter()
+            .enumerate()
+            .map(|(i, cell)| match old {
+                None if !previous.is_empty() => style(cell).green().to_string(),
+                Some(old) if i != UPTIME && old[i] != cell => {
+                    style(cell).yellow().bold().to_string()
+                }
+                _ => cell,
+            })
+            .collect();
+        table.push(row);
+    }
+    let Some(cursor) = cursor else {
+        return table.render(cols);
+    };
+    let mut lines: Vec<String> = table
+        .render(cols.map(|cols| cols.saturating_sub(2)))
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| match i {
+            i if i == cursor + 1 => format!("{} {}", style(">").cyan(), line),
+            _ => format!("  {}", line),
+        })
+        .collect();
+    if frame.tasks.is_empty() {
+        lines.push(format!("  {}", style("(no exec-ready tasks)").dim()));
+    }
+    lines
+}
+
+fn footer(service_name: &str, frame: &Frame, live: bool) -> String {
+    let mut parts = vec![format!(
+        "{} · {} exec-ready, {} not ready",
+        service_name,
+        frame.tasks.len(),
+        frame.not_ready
+    )];
+    if let Some(note) = &frame.note {
+        parts.push(note.clone());
+    }
+    if live {
+        parts.push(format!(
+            "every {}s · ↑↓ move · Enter exec · q quit",
+            REFRESH_SECS
+        ));
+    }
+    style(parts.join(" · ")).dim().to_string()
+}
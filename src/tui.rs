@@ -0,0 +1,624 @@
+//! `tui`: a full-screen browser of clusters, services and tasks.
+
+use crate::cache::TopologyCache;
+use crate::failure::Kind;
+use crate::{
+    build_client, cached_clusters, cached_services, connect, format_age, list_valid_tasks,
+    open_session, picker, short_id, show_zone, signals, sort_tasks, state, Args, ClusterInfo,
+    Selection, ServiceInfo, Session, TaskInfo,
+};
+use anyhow::Result;
+use aws_sdk_ecs::Client;
+use console::{measure_text_width, pad_str, style, truncate_str, Alignment, Key, Term};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const REFRESH_SECS: u64 = 10;
+
+// Smallest terminal the three panes fit in
+const MIN_COLS: u16 = 80;
+const MIN_ROWS: u16 = 16;
+
+// Lines of the details panel
+const DETAIL_LINES: usize = 6;
+
+const SEPARATOR: &str = " │ ";
+
+const CLUSTERS: usize = 0;
+const SERVICES: usize = 1;
+const TASKS: usize = 2;
+const TITLES: [&str; 3] = ["CLUSTERS", "SERVICES", "TASKS"];
+
+struct Browser {
+    clusters: Vec<ClusterInfo>,
+    // The services of `services_of` (a cluster ARN)
+    services: Vec<ServiceInfo>,
+    services_of: Option<String>,
+    // The tasks of `tasks_of` (cluster ARN and service name), once listed
+    tasks: Vec<TaskInfo>,
+    tasks_of: Option<(String, String)>,
+    not_ready: usize,
+    listed_at: Instant,
+    // The tasks are to be listed again
+    tasks_due: bool,
+    // Per pane: the cursor within the filtered entries, and the filter
+    cursors: [usize; 3],
+    filters: [String; 3],
+    focus: usize,
+    // Keys go to the filter of the focused pane
+    typing: bool,
+    // Shown in the footer until the next key
+    note: Option<String>,
+    // Bypass the cache on the next listing of the clusters, and of the
+    // services
+    refetch_clusters: bool,
+    refetch_services: bool,
+}
+
+// What a key asks the browser to do
+enum Action {
+    Stay,
+    Open(Box<Selection>),
+    Quit,
+}
+
+// What has to be listed before the next draw
+enum Load {
+    Clusters,
+    Services(String),
+    Tasks(String, String),
+}
+
+pub async fn run(args: &Args) -> Result<()> {
+    let term = Term::stdout();
+    if !term.is_term() || !picker::is_interactive() {
+        return Err(Kind::Usage
+            .error("tui needs a terminal; use `list` to print the clusters, services or tasks"));
+    }
+    let (rows, cols) = term.size();
+    if rows < MIN_ROWS || cols < MIN_COLS {
+        eprintln!(
+            "Note: the terminal is {}x{}, smaller than the {}x{} the browser needs; using the prompts",
+            cols, rows, MIN_COLS, MIN_ROWS
+        );
+        return connect(args, &Session::from_args(args)?).await;
+    }
+    let session = Session::from_args(args)?;
+    let client = build_client(&args.profile, args.region.as_deref()).await;
+    let region = client.config().region().map(|r| r.to_string());
+    let cache = TopologyCache::new(&args.profile, region.as_deref(), args.cache_ttl);
+    let (clusters, _) = cached_clusters(&client, &cache, args, args.refresh).await?;
+    if clusters.is_empty() {
+        return Err(Kind::NotFound.error("No clusters found."));
+    }
+    let mut browser = Browser::new(clusters, args.refresh);
+
+    loop {
+        term.hide_cursor()?;
+        let picked = browse(args, &client, &cache, &term, &mut browser).await;
+        term.show_cursor()?;
+        let Some(selection) = picked? else {
+            return Ok(());
+        };
+        // The session has the terminal to itself; a failed one is reported
+        // in the browser, unless a signal is ending the run
+        let result = open_session(args, &client, &session, *selection).await;
+        if signals::received().is_some() {
+            return result;
+        }
+        browser.note = result
+            .err()
+            .map(|err| style(format!("{:#}", err)).red().to_string());
+        browser.tasks_due = true;
+    }
+}
+
+// Draw and take keys until a task is opened (Some) or the user quits (None)
+async fn browse(
+    args: &Args,
+    client: &Client,
+    cache: &TopologyCache,
+    term: &Term,
+    browser: &mut Browser,
+) -> Result<Option<Box<Selection>>> {
+    let mut keys = read_keys();
+    let mut drawn = 0;
+    loop {
+        let load = browser.pending();
+        let (rows, cols) = term.size();
+        term.clear_last_lines(drawn)?;
+        let lines = browser.render(args, rows as usize, cols as usize, load.is_some());
+        for line in &lines {
+            term.write_line(line)?;
+        }
+        drawn = lines.len();
+
+        if let Some(load) = load {
+            browser.load(args, client, cache, load).await;
+            continue;
+        }
+        let timeout = Duration::from_secs(REFRESH_SECS).saturating_sub(browser.listed_at.elapsed());
+        let key = match tokio::task::block_in_place(|| keys.recv_timeout(timeout)) {
+            // Time to list the tasks again
+            Err(RecvTimeoutError::Timeout) => {
+                browser.listed_at = Instant::now();
+                browser.tasks_due = browser.focus == TASKS;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                keys = read_keys();
+                continue;
+            }
+            Ok(key) => key,
+        };
+        let last = ends_reading(&key);
+        match browser.key(key) {
+            Action::Stay if last => keys = read_keys(),
+            Action::Stay => {}
+            Action::Open(selection) => {
+                term.clear_last_lines(drawn)?;
+                return Ok(Some(selection));
+            }
+            Action::Quit => {
+                term.clear_last_lines(drawn)?;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+// Keys pressed, read on a thread of their own so the tasks keep refreshing.
+// The thread ends after a key that may leave the browser, so nothing
+// competes with the session for the terminal; it is started again when the
+// browser stays.
+fn read_keys() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let term = Term::stdout();
+        while let Ok(key) = term.read_key() {
+            let last = ends_reading(&key);
+            if sender.send(key).is_err() || last {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn ends_reading(key: &Key) -> bool {
+    matches!(key, Key::Enter | Key::Escape | Key::CtrlC | Key::Char('q'))
+}
+
+impl Browser {
+    fn new(clusters: Vec<ClusterInfo>, refresh: bool) -> Self {
+        Browser {
+            clusters,
+            services: Vec::new(),
+            services_of: None,
+            tasks: Vec::new(),
+            tasks_of: None,
+            not_ready: 0,
+            listed_at: Instant::now(),
+            tasks_due: false,
+            cursors: [0; 3],
+            filters: Default::default(),
+            focus: CLUSTERS,
+            typing: false,
+            note: None,
+            refetch_clusters: false,
+            refetch_services: refresh,
+        }
+    }
+
+    // The name each entry of a pane is filtered by
+    fn names(&self, pane: usize) -> Vec<&str> {
+        match pane {
+            CLUSTERS => self
+                .clusters
+                .iter()
+                .map(|c| c.cluster_name.as_str())
+                .collect(),
+            SERVICES => self
+                .services
+                .iter()
+                .map(|s| s.service_name.as_str())
+                .collect(),
+            _ => self.tasks.iter().map(|t| t.task_id.as_str()).collect(),
+        }
+    }
+
+    // Indexes of the entries of a pane that pass its filter
+    fn visible(&self, pane: usize) -> Vec<usize> {
+        let filter = self.filters[pane].to_lowercase();
+        self.names(pane)
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| name.to_lowercase().contains(&filter))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // The entry under the cursor of a pane
+    fn highlighted(&self, pane: usize) -> Option<usize> {
+        let visible = self.visible(pane);
+        visible
+            .get(self.cursors[pane].min(visible.len().saturating_sub(1)))
+            .copied()
+    }
+
+    fn cluster(&self) -> Option<&ClusterInfo> {
+        self.clusters.get(self.highlighted(CLUSTERS)?)
+    }
+
+    fn service(&self) -> Option<&ServiceInfo> {
+        // Services of another cluster are on their way out
+        if self.services_of.as_deref() != self.cluster().map(|c| c.arn.as_str()) {
+            return None;
+        }
+        self.services.get(self.highlighted(SERVICES)?)
+    }
+
+    fn task(&self) -> Option<&TaskInfo> {
+        let (cluster, service) = self.tasks_of.as_ref()?;
+        let current = (self.cluster()?, self.service()?);
+        if (cluster, service) != (&current.0.arn, &current.1.service_name) {
+            return None;
+        }
+        self.tasks.get(self.highlighted(TASKS)?)
+    }
+
+    fn pending(&self) -> Option<Load> {
+        if self.refetch_clusters {
+            return Some(Load::Clusters);
+        }
+        let cluster = self.cluster()?;
+        if self.refetch_services || self.services_of.as_deref() != Some(&cluster.arn) {
+            return Some(Load::Services(cluster.arn.clone()));
+        }
+        if self.focus != TASKS {
+            return None;
+        }
+        let service = self.service()?;
+        let wanted = (cluster.arn.clone(), service.service_name.clone());
+        (self.tasks_due || self.tasks_of.as_ref() != Some(&wanted))
+            .then_some(Load::Tasks(wanted.0, wanted.1))
+    }
+
+    async fn load(&mut self, args: &Args, client: &Client, cache: &TopologyCache, load: Load) {
+        match load {
+            Load::Clusters => {
+                self.refetch_clusters = false;
+                match cached_clusters(client, cache, args, true).await {
+                    Ok((clusters, _)) => self.clusters = clusters,
+                    Err(err) => self.note = Some(failure("list the clusters", &err)),
+                }
+            }
+            Load::Services(cluster_arn) => {
+                let refetch = std::mem::take(&mut self.refetch_services);
+                match cached_services(client, cache, &cluster_arn, args, refetch).await {
+                    Ok((services, _)) => self.services = services,
+                    Err(err) => {
+                        self.services = Vec::new();
+                        self.note = Some(failure("list the services", &err));
+                    }
+                }
+                if self.services_of.as_deref() != Some(&cluster_arn) {
+                    self.cursors[SERVICES] = 0;
+                }
+                self.services_of = Some(cluster_arn);
+            }
+            Load::Tasks(cluster_arn, service_name) => {
+                // Stay on the same task across refreshes
+                let selected = self.task().map(|task| task.task_id.clone());
+                let listing = list_valid_tasks(
+                    client,
+                    &cluster_arn,
+                    &service_name,
+                    args.max_results,
+                    args.max_tasks,
+                )
+                .await;
+                match listing {
+                    Ok(listing) => {
+                        self.tasks = listing.tasks;
+                        self.not_ready = listing.rejected.len();
+                        sort_tasks(&mut self.tasks, args);
+                    }
+                    Err(err) => {
+                        self.tasks = Vec::new();
+                        self.note = Some(failure("list the tasks", &err));
+                    }
+                }
+                self.tasks_of = Some((cluster_arn, service_name));
+                self.tasks_due = false;
+                self.listed_at = Instant::now();
+                let visible = self.visible(TASKS);
+                self.cursors[TASKS] = selected
+                    .and_then(|id| visible.iter().position(|&i| self.tasks[i].task_id == id))
+                    .unwrap_or(0);
+            }
+        }
+    }
+
+    fn key(&mut self, key: Key) -> Action {
+        self.note = None;
+        if self.typing {
+            let filter = &mut self.filters[self.focus];
+            match key {
+                Key::Char(c) if !c.is_control() => filter.push(c),
+                Key::Backspace => {
+                    filter.pop();
+                }
+                Key::Enter => self.typing = false,
+                Key::Escape => {
+                    filter.clear();
+                    self.typing = false;
+                }
+                Key::CtrlC => return Action::Quit,
+                _ => {}
+            }
+            self.cursors[self.focus] = 0;
+            return Action::Stay;
+        }
+        let count = self.visible(self.focus).len();
+        let cursor = &mut self.cursors[self.focus];
+        match key {
+            Key::ArrowUp | Key::Char('k') => *cursor = cursor.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') if *cursor + 1 < count => *cursor += 1,
+            Key::Home | Key::Char('g') => *cursor = 0,
+            Key::End | Key::Char('G') => *cursor = count.saturating_sub(1),
+            Key::ArrowLeft | Key::Char('h') | Key::BackTab => {
+                self.focus = self.focus.saturating_sub(1)
+            }
+            Key::ArrowRight | Key::Char('l') | Key::Tab => self.enter(),
+            Key::Enter if self.focus == TASKS => {
+                let (Some(cluster), Some(service), Some(task)) =
+                    (self.cluster(), self.service(), self.task())
+                else {
+                    return Action::Stay;
+                };
+                return Action::Open(Box::new(Selection {
+                    cluster_arn: cluster.arn.clone(),
+                    service: service.clone(),
+                    task: task.clone(),
+                    container: None,
+                }));
+            }
+            Key::Enter => self.enter(),
+            Key::Char('/') => self.typing = true,
+            Key::Char('r') => {
+                self.refetch_clusters = self.focus == CLUSTERS;
+                self.refetch_services = true;
+                self.tasks_due = true;
+            }
+            // Esc drops the filter before it quits
+            Key::Escape if !self.filters[self.focus].is_empty() => {
+                self.filters[self.focus].clear();
+                self.cursors[self.focus] = 0;
+            }
+            Key::Char('q') | Key::Escape | Key::CtrlC => return Action::Quit,
+            _ => {}
+        }
+        Action::Stay
+    }
+
+    // Move the focus into the highlighted cluster or service
+    fn enter(&mut self) {
+        let ready = match self.focus {
+            CLUSTERS => self.cluster().is_some(),
+            SERVICES => self.service().is_some(),
+            _ => false,
+        };
+        if ready {
+            self.focus += 1;
+        }
+    }
+
+    fn render(&self, args: &Args, rows: usize, cols: usize, loading: bool) -> Vec<String> {
+        if rows < MIN_ROWS as usize || cols < MIN_COLS as usize {
+            return vec![style(format!(
+                "The terminal is too small for the browser ({}x{} at least) · q quit",
+                MIN_COLS, MIN_ROWS
+            ))
+            .dim()
+            .to_string()];
+        }
+        let width = (cols - 2 * measure_text_width(SEPARATOR)) / 3;
+        // Title, rule, details and footer around the lists; one row is left
+        // free so the screen doesn't scroll
+        let height = rows - DETAIL_LINES - 4;
+        let panes: Vec<Vec<String>> = (0..3)
+            .map(|pane| self.pane(args, pane, width, height))
+            .collect();
+
+        let mut lines = Vec::new();
+        for row in 0..=height {
+            let cells: Vec<&str> = panes.iter().map(|pane| pane[row].as_str()).collect();
+            lines.push(cells.join(&style(SEPARATOR).dim().to_string()));
+        }
+        lines.push(style("─".repeat(cols - 1)).dim().to_string());
+        let mut details = self.details(args);
+        details.resize(DETAIL_LINES, String::new());
+        lines.extend(
+            details
+                .iter()
+                .map(|line| truncate_str(line, cols - 1, "…").to_string()),
+        );
+        lines.push(truncate_str(&self.footer(loading), cols - 1, "…").to_string());
+        lines
+    }
+
+    // The title and `height` entries of a pane, each `width` wide
+    fn pane(&self, args: &Args, pane: usize, width: usize, height: usize) -> Vec<String> {
+        let focused = pane == self.focus;
+        let mut title = TITLES[pane].to_string();
+        if !self.filters[pane].is_empty() || (focused && self.typing) {
+            title.push_str(&format!(" /{}", self.filters[pane]));
+        }
+        let title = if focused {
+            style(title).bold().cyan().to_string()
+        } else {
+            style(title).bold().to_string()
+        };
+        let mut lines = vec![fit(&title, width)];
+
+        let entries = self.entries(args, pane);
+        let visible = self.visible(pane);
+        let cursor = self.cursors[pane].min(visible.len().saturating_sub(1));
+        let offset = (cursor + 1).saturating_sub(height);
+        for (row, &i) in visible.iter().enumerate().skip(offset).take(height) {
+            let line = match (row == cursor, focused) {
+                (true, true) => format!("{} {}", style(">").cyan(), entries[i]),
+                (true, false) => format!("{} {}", style("›").dim(), entries[i]),
+                _ => format!("  {}", entries[i]),
+            };
+            lines.push(fit(&line, width));
+        }
+        if visible.is_empty() {
+            let empty = match pane {
+                _ if !self.filters[pane].is_empty() => "(no match)",
+                SERVICES if self.services_of.is_none() => "",
+                TASKS if self.tasks_of.is_none() => "(Enter a service)",
+                TASKS => "(no exec-ready tasks)",
+                _ => "(none)",
+            };
+            lines.push(fit(&format!("  {}", style(empty).dim()), width));
+        }
+        lines.resize(height + 1, " ".repeat(width));
+        lines
+    }
+
+    // Every entry of a pane, filtered or not
+    fn entries(&self, args: &Args, pane: usize) -> Vec<String> {
+        match pane {
+            CLUSTERS => self
+                .clusters
+                .iter()
+                .map(|c| c.cluster_name.clone())
+                .collect(),
+            SERVICES => self
+                .services
+                .iter()
+                .map(|s| s.service_name.clone())
+                .collect(),
+            _ => self
+                .tasks
+                .iter()
+                .map(|task| {
+                    format!(
+                        "{}  {}",
+                        short_id(&task.task_id),
+                        task.status_label(show_zone(args))
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    // The details of the highlighted entry of the focused pane
+    fn details(&self, args: &Args) -> Vec<String> {
+        let label = |name: &str| style(format!("{:<10}", name)).dim().to_string();
+        match self.focus {
+            CLUSTERS => {
+                let Some(cluster) = self.cluster() else {
+                    return Vec::new();
+                };
+                let mut lines = vec![
+                    format!(
+                        "{}{}",
+                        label("Cluster"),
+                        style(&cluster.cluster_name).bold()
+                    ),
+                    format!("{}{}", label("ARN"), cluster.arn),
+                ];
+                if let Some(stats) = cluster.stats() {
+                    lines.push(format!("{}{}", label("Running"), stats));
+                }
+                lines
+            }
+            SERVICES => {
+                let Some(service) = self.service() else {
+                    return Vec::new();
+                };
+                vec![
+                    format!(
+                        "{}{}",
+                        label("Service"),
+                        style(&service.service_name).bold()
+                    ),
+                    format!("{}{}", label("ARN"), service.arn),
+                ]
+            }
+            _ => {
+                let Some(task) = self.task() else {
+                    return Vec::new();
+                };
+                let mut facts = Vec::new();
+                if let Some(started) = task.started_at {
+                    facts.push(format!(
+                        "started {}",
+                        format_age(state::now_secs() - started)
+                    ));
+                }
+                if let Some(revision) = task.revision {
+                    facts.push(format!("revision {}", revision));
+                }
+                if let Some(zone) = &task.availability_zone {
+                    facts.push(zone.clone());
+                }
+                let containers: Vec<String> = task
+                    .containers
+                    .iter()
+                    .map(|name| match task.runtime_id(name) {
+                        Some(id) => format!("{} ({})", name, short_id(id)),
+                        None => name.clone(),
+                    })
+                    .collect();
+                vec![
+                    format!("{}{}", label("Task"), style(&task.task_id).bold()),
+                    format!("{}{}", label("ARN"), task.arn),
+                    format!("{}{}", label("Status"), task.status_label(show_zone(args))),
+                    format!("{}{}", label("Family"), task.task_name),
+                    format!("{}{}", label("Started"), facts.join(" · ")),
+                    format!("{}{}", label("Containers"), containers.join(", ")),
+                ]
+            }
+        }
+    }
+
+    fn footer(&self, loading: bool) -> String {
+        if loading {
+            return style("Listing…").dim().to_string();
+        }
+        if let Some(note) = &self.note {
+            return note.clone();
+        }
+        if self.typing {
+            return style("Type to filter · Enter keep · Esc clear")
+                .dim()
+                .to_string();
+        }
+        let mut parts = Vec::new();
+        if self.focus == TASKS && self.tasks_of.is_some() {
+            parts.push(format!(
+                "{} exec-ready, {} not ready · every {}s",
+                self.tasks.len(),
+                self.not_ready,
+                REFRESH_SECS
+            ));
+        }
+        parts.push("←→↑↓ hjkl move · Enter open · / filter · r refresh · q quit".to_string());
+        style(parts.join(" · ")).dim().to_string()
+    }
+}
+
+fn failure(what: &str, err: &anyhow::Error) -> String {
+    style(format!("Could not {}: {:#}", what, err))
+        .red()
+        .to_string()
+}
+
+// `text` cut or padded to exactly `width` columns
+fn fit(text: &str, width: usize) -> String {
+    pad_str(text, width, Alignment::Left, Some("…")).to_string()
+}
@@ -0,0 +1,129 @@
+//! Opt-in check for a newer release on GitHub.
+
+use crate::json::{self, Value};
+use crate::proxy;
+use crate::state;
+use anyhow::{anyhow, Result};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+const RELEASES_URL: &str = "https://api.github.com/repos/Dowwie/ecs_remote/releases/latest";
+const UPDATE_FILE: &str = "update.json";
+const CHECK_INTERVAL_SECS: i64 = 24 * 60 * 60;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub enum UpdateCheck {
+    // Answered from the on-disk cache
+    Cached(Option<String>),
+    // Waiting on GitHub
+    Pending(JoinHandle<Option<String>>),
+}
+
+impl UpdateCheck {
+    // Look up the latest release tag, from the cache when it is fresh.
+    pub fn start() -> Self {
+        let cached = state::read(UPDATE_FILE).ok();
+        if let Some(cached) = &cached {
+            let checked_at = cached
+                .get("checked_at")
+                .and_then(Value::as_i64)
+                .unwrap_or(0);
+            if state::now_secs() - checked_at < CHECK_INTERVAL_SECS {
+                return UpdateCheck::Cached(cached.str_field("latest"));
+            }
+        }
+        // Record the attempt up front, so that an offline run, or one that
+        // ends before the answer, doesn't make the next run ask again. The
+        // last known release is still reported until there is an answer.
+        let latest = cached.and_then(|cached| cached.str_field("latest"));
+        save(latest.as_deref());
+        UpdateCheck::Pending(tokio::spawn(refresh()))
+    }
+
+    // Print a notice if the answer names a newer version. A pending check
+    // is never waited for.
+    pub async fn report(self) {
+        let latest = match self {
+            UpdateCheck::Cached(latest) => latest,
+            UpdateCheck::Pending(handle) if handle.is_finished() => handle.await.ok().flatten(),
+            // The answer is cached as soon as it arrives, so a check cut
+            // short here is reported by a later run
+            UpdateCheck::Pending(handle) => {
+                handle.abort();
+                None
+            }
+        };
+        let current = env!("CARGO_PKG_VERSION");
+        if let Some(latest) = latest {
+            if is_newer(&latest, current) {
+                eprintln!(
+                    "A newer ecs_remote is available: {} (you have {}). See https://github.com/Dowwie/ecs_remote/releases",
+                    latest, current
+                );
+            }
+        }
+    }
+}
+
+async fn refresh() -> Option<String> {
+    let latest = tokio::time::timeout(REQUEST_TIMEOUT, fetch_latest())
+        .await
+        .ok()?
+        .ok()?;
+    save(latest.as_deref());
+    latest
+}
+
+fn save(latest: Option<&str>) {
+    let _ = state::update(UPDATE_FILE, |value| {
+        let mut entry = Value::object();
+        entry.set("checked_at", state::now_secs());
+        if let Some(latest) = latest {
+            entry.set("latest", latest);
+        }
+        *value = entry;
+    });
+}
+
+// The latest release tag, or None when the repository has no releases
+async fn fetch_latest() -> Result<Option<String>> {
+    // --proxy-url and --ca-bundle apply here too
+    let client = hyper::Client::builder().build::<_, hyper::Body>(proxy::connector());
+    let request = hyper::Request::get(RELEASES_URL)
+        .header(
+            "User-Agent",
+            concat!("ecs_remote/", env!("CARGO_PKG_VERSION")),
+        )
+        .header("Accept", "application/vnd.github+json")
+        .body(hyper::Body::empty())?;
+
+    let response = client.request(request).await?;
+    if response.status() == hyper::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !response.status().is_success() {
+        return Err(anyhow!("GitHub answered {}", response.status()));
+    }
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    let release = json::parse(std::str::from_utf8(&body)?)?;
+    release
+        .str_field("tag_name")
+        .map(Some)
+        .ok_or_else(|| anyhow!("Release without a tag_name"))
+}
+
+// Compare dotted numeric versions, ignoring a leading `v` and any
+// pre-release suffix
+fn is_newer(latest: &str, current: &str) -> bool {
+    fn parts(version: &str) -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+    parts(latest) > parts(current)
+}
@@ -0,0 +1,402 @@
+//! `watch`: follow a service's deployments until the rollout settles.
+
+use crate::failure::{self, Kind};
+use crate::output::Table;
+use crate::{arn, build_client, choose_service, notify, picker, ratelimit, Args};
+use anyhow::{anyhow, Result};
+use aws_sdk_ecs::types::{Deployment, DeploymentRolloutState, Service};
+use aws_sdk_ecs::Client;
+use console::{style, Key, Term};
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const POLL_SECS: u64 = 5;
+
+// Service events kept on screen
+const EVENT_LINES: usize = 8;
+
+const HEADERS: [&str; 7] = [
+    "DEPLOYMENT",
+    "REVISION",
+    "RUNNING",
+    "PENDING",
+    "DESIRED",
+    "FAILED",
+    "ROLLOUT",
+];
+
+// Where the watched rollout stands after a poll
+enum Progress {
+    Rolling,
+    // Steady: one deployment, all of its tasks running
+    Stable,
+    Failed(String),
+}
+
+// What the view shows of one describe_services
+struct Poll {
+    // Deployment ID and its cells, PRIMARY first
+    rows: Vec<(String, Vec<String>)>,
+    breaker: String,
+    // (event ID, seconds since the epoch, message), oldest first
+    events: Vec<(String, i64, String)>,
+    progress: Progress,
+}
+
+pub async fn run(args: &Args, until_stable: bool, timeout: Option<u64>) -> Result<()> {
+    let client = build_client(&args.profile, args.region.as_deref()).await;
+    let (cluster_arn, service, _) = choose_service(args, &client).await?;
+    let service_name = service.service_name;
+    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs(secs));
+
+    let first = describe(&client, &cluster_arn, &service_name).await?;
+    // The rollout under way when the watch started, or the failed one a
+    // rollback is undoing; a deployment started later takes its place
+    let failed = first
+        .deployments
+        .iter()
+        .flatten()
+        .find(|d| d.rollout_state == Some(DeploymentRolloutState::Failed));
+    let mut watched = failed.or(primary(&first)).and_then(|d| d.id.clone());
+    let mut rolling = in_progress(&first);
+    let mut poll = snapshot(&first, watched.as_deref());
+    // The events from before the watch are context on screen, but not news
+    // for a log
+    let mut seen: HashSet<String> = poll.events.iter().map(|(id, _, _)| id.clone()).collect();
+
+    let term = Term::stdout();
+    let live = term.is_term() && picker::is_interactive();
+    let mut keys = live.then(read_keys);
+    let mut drawn = 0;
+    let mut previous: Vec<(String, Vec<String>)> = Vec::new();
+    if live {
+        term.hide_cursor()?;
+    } else {
+        println!("{}", poll.breaker);
+    }
+    let outcome = loop {
+        if live {
+            term.clear_last_lines(drawn)?;
+            let lines = render(&service_name, &poll, &previous, until_stable);
+            for line in &lines {
+                term.write_line(line)?;
+            }
+            drawn = lines.len();
+        } else {
+            for line in log_lines(&poll, &previous, &mut seen) {
+                println!("{}", line);
+            }
+        }
+
+        match &poll.progress {
+            Progress::Failed(why) => break Err(Kind::Deployment.error(why.clone())),
+            Progress::Stable if until_stable || rolling => break Ok(()),
+            _ => {}
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break Err(Kind::Deployment.error(format!(
+                "Service {} isn't stable after {}s",
+                service_name,
+                timeout.unwrap_or_default()
+            )));
+        }
+
+        // Wait out the poll interval, or until a key ends the watch
+        let wait = Duration::from_secs(POLL_SECS);
+        if let Some(receiver) = &keys {
+            match tokio::task::block_in_place(|| receiver.recv_timeout(wait)) {
+                Ok(Key::Char('q')) | Ok(Key::Escape) => break Ok(()),
+                Ok(Key::CtrlC) => break Err(Kind::Aborted.error("Stopped watching")),
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => keys = Some(read_keys()),
+            }
+        } else {
+            tokio::time::sleep(wait).await;
+        }
+
+        let service = describe(&client, &cluster_arn, &service_name).await?;
+        let primary_id = primary(&service).and_then(|d| d.id.clone());
+        // A new deployment replaces the watched one, unless it is the
+        // rollback of a failure
+        if primary_id != watched && !watched_failed(&service, watched.as_deref()) {
+            watched = primary_id;
+        }
+        rolling |= in_progress(&service);
+        previous = std::mem::take(&mut poll.rows);
+        poll = snapshot(&service, watched.as_deref());
+    };
+    if live {
+        term.show_cursor()?;
+    }
+    match &outcome {
+        Ok(()) if matches!(poll.progress, Progress::Stable) => {
+            let summary = poll
+                .rows
+                .first()
+                .map(|(_, cells)| format!("{}, {}/{} running", cells[1], cells[2], cells[4]))
+                .unwrap_or_default();
+            eprintln!(
+                "{} {} is stable: {}",
+                style("✔").green(),
+                service_name,
+                summary
+            );
+            notify::send(&format!("{} is stable: {}", service_name, summary));
+        }
+        // Quitting or Ctrl-C is not news to the one who pressed it
+        Err(err) if failure::classify(err) == Kind::Deployment => {
+            notify::send(&format!("{} is not stable: {}", service_name, err));
+        }
+        _ => {}
+    }
+    outcome
+}
+
+async fn describe(client: &Client, cluster_arn: &str, service_name: &str) -> Result<Service> {
+    ratelimit::acquire().await;
+    client
+        .describe_services()
+        .cluster(cluster_arn)
+        .services(service_name)
+        .send()
+        .await?
+        .services
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            anyhow!(
+                "Service {} not found in cluster {}",
+                service_name,
+                arn::cluster_name(cluster_arn)
+            )
+        })
+}
+
+fn primary(service: &Service) -> Option<&Deployment> {
+    service
+        .deployments
+        .iter()
+        .flatten()
+        .find(|d| d.status.as_deref() == Some("PRIMARY"))
+}
+
+fn in_progress(service: &Service) -> bool {
+    service.deployments.iter().flatten().count() > 1
+        || primary(service)
+            .is_some_and(|d| d.rollout_state == Some(DeploymentRolloutState::InProgress))
+}
+
+fn watched_failed(service: &Service, watched: Option<&str>) -> bool {
+    service.deployments.iter().flatten().any(|d| {
+        d.id.as_deref() == watched && d.rollout_state == Some(DeploymentRolloutState::Failed)
+    })
+}
+
+fn snapshot(service: &Service, watched: Option<&str>) -> Poll {
+    let mut deployments: Vec<&Deployment> = service.deployments.iter().flatten().collect();
+    deployments.sort_by_key(|d| d.status.as_deref() != Some("PRIMARY"));
+    let rows = deployments
+        .iter()
+        .map(|d| {
+            let cells = vec![
+                d.status.clone().unwrap_or_else(|| "-".to_string()),
+                d.task_definition
+                    .as_deref()
+                    .map(arn::task_definition)
+                    .unwrap_or("-")
+                    .to_string(),
+                d.running_count.to_string(),
+                d.pending_count.to_string(),
+                d.desired_count.to_string(),
+                d.failed_tasks.to_string(),
+                d.rollout_state
+                    .as_ref()
+                    .map(|state| state.as_str().to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            ];
+            (d.id.clone().unwrap_or_default(), cells)
+        })
+        .collect();
+
+    let breaker = match service
+        .deployment_configuration
+        .as_ref()
+        .and_then(|config| config.deployment_circuit_breaker.as_ref())
+    {
+        Some(breaker) if breaker.enable && breaker.rollback => {
+            "Circuit breaker: on, rolls back".to_string()
+        }
+        Some(breaker) if breaker.enable => "Circuit breaker: on, no rollback".to_string(),
+        _ => "Circuit breaker: off".to_string(),
+    };
+
+    let mut events: Vec<(String, i64, String)> = service
+        .events
+        .iter()
+        .flatten()
+        .map(|event| {
+            (
+                event.id.clone().unwrap_or_default(),
+                event.created_at.map(|at| at.secs()).unwrap_or_default(),
+                event.message.clone().unwrap_or_default(),
+            )
+        })
+        .collect();
+    events.reverse();
+
+    let failed = deployments.iter().find(|d| {
+        d.id.as_deref() == watched && d.rollout_state == Some(DeploymentRolloutState::Failed)
+    });
+    let progress = match (failed, primary(service)) {
+        (Some(failed), primary) => {
+            let mut why = format!(
+                "Deployment {} of {} failed",
+                failed.id.as_deref().unwrap_or("-"),
+                failed
+                    .task_definition
+                    .as_deref()
+                    .map(arn::task_definition)
+                    .unwrap_or("-")
+            );
+            if let Some(reason) = &failed.rollout_state_reason {
+                why.push_str(&format!(": {}", reason));
+            }
+            if let Some(rollback) = primary.filter(|p| p.id != failed.id) {
+                why.push_str(&format!(
+                    " (rolled back to {})",
+                    rollback
+                        .task_definition
+                        .as_deref()
+                        .map(arn::task_definition)
+                        .unwrap_or("-")
+                ));
+            }
+            Progress::Failed(why)
+        }
+        (None, Some(primary))
+            if deployments.len() == 1
+                && primary.running_count == primary.desired_count
+                && primary.pending_count == 0
+                && primary.rollout_state != Some(DeploymentRolloutState::InProgress) =>
+        {
+            Progress::Stable
+        }
+        _ => Progress::Rolling,
+    };
+
+    Poll {
+        rows,
+        breaker,
+        events,
+        progress,
+    }
+}
+
+// Keys pressed, read on a thread of their own so the view keeps refreshing
+fn read_keys() -> Receiver<Key> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let term = Term::stdout();
+        while let Ok(key) = term.read_key() {
+            let last = matches!(key, Key::Escape | Key::CtrlC | Key::Char('q'));
+            if sender.send(key).is_err() || last {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// The deployments table with changed cells in yellow and new deployments in
+// green, the newest events and a footer
+fn render(
+    service_name: &str,
+    poll: &Poll,
+    previous: &[(String, Vec<String>)],
+    until_stable: bool,
+) -> Vec<String> {
+    let mut table = Table::new(&HEADERS);
+    for (id, cells) in &poll.rows {
+        let old = previous.iter().find(|(old, _)| old == id).map(|(_, c)| c);
+        let row = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| match old {
+                None if !previous.is_empty() => style(cell).green().to_string(),
+                Some(old) if old[i] != *cell => style(cell).yellow().bold().to_string(),
+                _ => rollout_style(cell),
+            })
+            .collect();
+        table.push(row);
+    }
+    let (_, cols) = Term::stdout().size();
+    let mut lines = table.render(Some(cols as usize));
+    lines.push(style(&poll.breaker).dim().to_string());
+    lines.push(String::new());
+    let start = poll.events.len().saturating_sub(EVENT_LINES);
+    for (_, at, message) in &poll.events[start..] {
+        let line = format!("{} {}", style(clock(*at)).dim(), message);
+        lines.push(console::truncate_str(&line, cols as usize - 1, "…").to_string());
+    }
+    let state = match &poll.progress {
+        Progress::Rolling => "rolling out".to_string(),
+        Progress::Stable => "stable".to_string(),
+        Progress::Failed(_) => "failed".to_string(),
+    };
+    let mut footer = format!(
+        "{} · {} · every {}s · q quit",
+        service_name, state, POLL_SECS
+    );
+    if until_stable {
+        footer.push_str(" · ends when stable");
+    }
+    lines.push(style(footer).dim().to_string());
+    lines
+}
+
+// Changed deployments and the events not printed yet, for logs
+fn log_lines(
+    poll: &Poll,
+    previous: &[(String, Vec<String>)],
+    seen: &mut HashSet<String>,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (id, cells) in &poll.rows {
+        if previous
+            .iter()
+            .any(|(old, old_cells)| old == id && old_cells == cells)
+        {
+            continue;
+        }
+        let described: Vec<String> = HEADERS
+            .iter()
+            .zip(cells)
+            .skip(1)
+            .map(|(header, cell)| format!("{}={}", header.to_lowercase(), cell))
+            .collect();
+        lines.push(format!("{} {} {}", cells[0], id, described.join(" ")));
+    }
+    for (id, at, message) in &poll.events {
+        if seen.insert(id.clone()) {
+            lines.push(format!("{} {}", clock(*at), message));
+        }
+    }
+    lines
+}
+
+fn rollout_style(cell: &str) -> String {
+    match cell {
+        "COMPLETED" => style(cell).green().to_string(),
+        "FAILED" => style(cell).red().bold().to_string(),
+        "IN_PROGRESS" => style(cell).cyan().to_string(),
+        _ => cell.to_string(),
+    }
+}
+
+// `14:03:27` (UTC) for seconds since the epoch
+fn clock(secs: i64) -> String {
+    let day = secs.rem_euclid(86_400);
+    format!("{:02}:{:02}:{:02}", day / 3600, day % 3600 / 60, day % 60)
+}